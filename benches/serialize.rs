@@ -0,0 +1,59 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use vrc_oscquery::node::{Access, OscNode};
+
+fn small_tree() -> OscNode {
+    let mut root = OscNode::new_container("/");
+    for i in 0..10 {
+        OscNode::add_method(&mut root, &format!("/test/param{}", i), Access::ReadWrite, "f");
+    }
+    root
+}
+
+/// Roughly what VRChat advertises for an avatar with 200 parameters
+fn vrchat_avatar_tree() -> OscNode {
+    let mut root = OscNode::new_container("/");
+    OscNode::add_method(&mut root, "/avatar/change", Access::Write, "s");
+    for i in 0..200 {
+        let typetag = match i % 3 {
+            0 => "f",
+            1 => "i",
+            _ => "T",
+        };
+        OscNode::add_method(
+            &mut root,
+            &format!("/avatar/parameters/Param{}", i),
+            Access::ReadWrite,
+            typetag,
+        );
+    }
+    root
+}
+
+fn serialize_small_tree(c: &mut Criterion) {
+    let root = small_tree();
+    c.bench_function("serialize_small_tree", |b| {
+        b.iter(|| serde_json::to_string(black_box(&root)).unwrap())
+    });
+}
+
+fn serialize_vrchat_avatar_tree(c: &mut Criterion) {
+    let root = vrchat_avatar_tree();
+    c.bench_function("serialize_vrchat_avatar_tree", |b| {
+        b.iter(|| serde_json::to_string(black_box(&root)).unwrap())
+    });
+}
+
+fn deserialize_vrchat_avatar_tree(c: &mut Criterion) {
+    let json = serde_json::to_string(&vrchat_avatar_tree()).unwrap();
+    c.bench_function("deserialize_vrchat_avatar_tree", |b| {
+        b.iter(|| serde_json::from_str::<OscNode>(black_box(&json)).unwrap())
+    });
+}
+
+criterion_group!(
+    benches,
+    serialize_small_tree,
+    serialize_vrchat_avatar_tree,
+    deserialize_vrchat_avatar_tree
+);
+criterion_main!(benches);
@@ -0,0 +1,44 @@
+use vrc_oscquery::node::{Access, OscNode};
+
+// MessagePack vs JSON for an avatar-sized tree, run with
+// `cargo run --example msgpack_size --features msgpack`
+fn main() {
+    let mut root = OscNode::new_container("/");
+    let builtin = [
+        ("GestureLeft", "i", serde_json::json!([0])),
+        ("GestureRight", "i", serde_json::json!([0])),
+        ("GestureLeftWeight", "f", serde_json::json!([0.0])),
+        ("GestureRightWeight", "f", serde_json::json!([0.0])),
+        ("VRCEmote", "i", serde_json::json!([0])),
+        ("Grounded", "T", serde_json::json!([true])),
+        ("Seated", "F", serde_json::json!([false])),
+        ("AFK", "F", serde_json::json!([false])),
+        ("Viseme", "i", serde_json::json!([0])),
+        ("Voice", "f", serde_json::json!([0.0])),
+    ];
+    for (name, typetag, value) in builtin {
+        let path = format!("/avatar/parameters/{}", name);
+        OscNode::add_method_with_value(&mut root, &path, Access::ReadWrite, typetag, value).unwrap();
+    }
+    for i in 0..100 {
+        let (typetag, value) = match i % 3 {
+            0 => ("T", serde_json::json!([true])),
+            1 => ("i", serde_json::json!([i])),
+            _ => ("f", serde_json::json!([0.5])),
+        };
+        let path = format!("/avatar/parameters/Toggle_{}", i);
+        OscNode::add_method_with_value(&mut root, &path, Access::ReadWrite, typetag, value).unwrap();
+    }
+    OscNode::add_method(&mut root, "/avatar/change", Access::Read, "s");
+
+    let json = serde_json::to_vec(&root).unwrap();
+    let msgpack = OscNode::to_msgpack(&root).unwrap();
+    assert_eq!(OscNode::from_msgpack(&msgpack).unwrap(), root);
+
+    println!("JSON:        {} bytes", json.len());
+    println!("MessagePack: {} bytes", msgpack.len());
+    println!(
+        "{:.0}% smaller",
+        100.0 * (1.0 - msgpack.len() as f64 / json.len() as f64)
+    );
+}
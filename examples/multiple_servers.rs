@@ -0,0 +1,25 @@
+use std::net::UdpSocket;
+use vrc_oscquery::node::Access;
+use vrc_oscquery::server::OscQueryServerBuilder;
+use vrc_oscquery::OscQueryError;
+
+// Two independent OSCQuery services from one process, each with its own
+// name, ports and mDNS daemon
+#[tokio::main]
+async fn main() -> Result<(), OscQueryError> {
+    let avatar_socket = UdpSocket::bind("127.0.0.1:0")?;
+    let haptics_socket = UdpSocket::bind("127.0.0.1:0")?;
+
+    let avatar = OscQueryServerBuilder::new("AvatarTree", avatar_socket.local_addr()?.port())
+        .with_vrchat_avatar_receiver()
+        .build_and_run();
+    let haptics = OscQueryServerBuilder::new("HapticsTree", haptics_socket.local_addr()?.port())
+        .with_method_value("/haptics/intensity", Access::ReadWrite, "f", serde_json::json!([0.0]))
+        .build_and_run();
+
+    let (avatar, haptics) = tokio::join!(avatar, haptics);
+    let (avatar, haptics) = (avatar?, haptics?);
+
+    tokio::join!(avatar.wait_until_shutdown(), haptics.wait_until_shutdown());
+    Ok(())
+}
@@ -1,21 +1,18 @@
 use std::net::UdpSocket;
-use std::thread::sleep;
-use std::time::Duration;
-use vrc_oscquery::client::discover_vrchat_oscquery;
-use vrc_oscquery::server::{OscQueryServerBuilder, RunningServer};
+use vrc_oscquery::server::OscQueryServerBuilder;
+use vrc_oscquery::OscQueryError;
 
 #[tokio::main]
-async fn main() {
-    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
-    let udp_port = socket.local_addr().unwrap().port();
+async fn main() -> Result<(), OscQueryError> {
+    let socket = UdpSocket::bind("127.0.0.1:0")?;
+    let udp_port = socket.local_addr()?.port();
 
     println!("Starting OSC receiver on UDP port {}", udp_port);
 
-    let result = OscQueryServerBuilder::new("TestApp", udp_port)
+    let server = OscQueryServerBuilder::new("TestApp", udp_port)
         .with_vrchat_avatar_receiver()
-        .build_and_run().await.unwrap();
+        .build_and_run().await?;
 
-    loop {
-
-    }
-}
\ No newline at end of file
+    server.wait_until_shutdown().await;
+    Ok(())
+}
@@ -0,0 +1,39 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use vrc_oscquery::node::{path_components, validate_osc_path, OscNode};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(path) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    // The lenient version takes anything but never makes malformed paths
+    let mut root = OscNode::new_container("/");
+    let node = OscNode::ensure_path(&mut root, path);
+    assert!(node.full_path.starts_with('/'), "{:?} from {:?}", node.full_path, path);
+    for node in OscNode::find(&root, |_| true) {
+        assert!(
+            path_components(&node.full_path).is_ok(),
+            "{:?} from {:?}",
+            node.full_path,
+            path
+        );
+    }
+
+    // The checked version either makes only valid addresses or nothing at all
+    let mut root = OscNode::new_container("/");
+    match OscNode::try_ensure_path(&mut root, path) {
+        Ok(_) => {
+            for node in OscNode::find(&root, |_| true) {
+                assert!(
+                    validate_osc_path(&node.full_path).is_ok(),
+                    "{:?} from {:?}",
+                    node.full_path,
+                    path
+                );
+            }
+        }
+        Err(_) => assert_eq!(OscNode::node_count(&root), 1, "{:?}", path),
+    }
+});
@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use vrc_oscquery::node::OscNode;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(node) = serde_json::from_slice::<OscNode>(data) else {
+        return;
+    };
+
+    // Whatever we accepted must survive a serialize/deserialize round trip
+    let first = serde_json::to_value(&node).unwrap();
+    let reparsed: OscNode = serde_json::from_value(first.clone()).unwrap();
+    let second = serde_json::to_value(&reparsed).unwrap();
+    assert_eq!(first, second);
+});
@@ -0,0 +1,63 @@
+#![no_main]
+
+use std::collections::BTreeSet;
+
+use libfuzzer_sys::fuzz_target;
+use vrc_oscquery::node::{ancestors, Access, OscNode};
+
+const SEGMENT_CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789-";
+const TYPETAGS: &[&str] = &[
+    "i", "f", "s", "b", "h", "t", "d", "S", "c", "r", "m", "T", "F", "N", "I", "ff", "sTT", "[if]",
+];
+const ACCESS: [Access; 4] = [Access::None, Access::Read, Access::Write, Access::ReadWrite];
+
+// Builds a random tree out of the input, one method per few bytes, and
+// checks the invariants every tree should hold
+fuzz_target!(|data: &[u8]| {
+    let mut bytes = data.iter().map(|&b| b as usize);
+    let mut root = OscNode::new_container("/");
+    let mut containers = BTreeSet::new();
+    let mut methods = BTreeSet::new();
+
+    while let Some(depth) = bytes.next() {
+        let mut path = String::new();
+        for _ in 0..depth % 4 + 1 {
+            path.push('/');
+            for _ in 0..bytes.next().unwrap_or(0) % 4 + 1 {
+                let c = SEGMENT_CHARS[bytes.next().unwrap_or(0) % SEGMENT_CHARS.len()];
+                path.push(c as char);
+            }
+        }
+        let typetag = TYPETAGS[bytes.next().unwrap_or(0) % TYPETAGS.len()];
+        let access = ACCESS[bytes.next().unwrap_or(0) % ACCESS.len()];
+
+        // Re-adding a method or putting one on top of another would replace
+        // nodes, which makes the expected count depend on insertion order
+        let parents: Vec<String> = ancestors(&path).into_iter().skip(1).map(String::from).collect();
+        if containers.contains(&path)
+            || methods.contains(&path)
+            || parents.iter().any(|parent| methods.contains(parent))
+        {
+            continue;
+        }
+
+        OscNode::add_method(&mut root, &path, access, typetag);
+        containers.extend(parents);
+        methods.insert(path);
+    }
+
+    // The root is a container too
+    let count = OscNode::node_count(&root);
+    assert_eq!(count, 1 + containers.len() + methods.len());
+    assert_eq!(root.iter().count(), count);
+
+    for node in root.iter() {
+        assert!(node.full_path.starts_with('/'), "{:?}", node.full_path);
+    }
+
+    // serde_json::Value sorts keys, so this is stable across HashMaps
+    let first = serde_json::to_value(&root).unwrap().to_string();
+    let reparsed: OscNode = serde_json::from_str(&first).unwrap();
+    let second = serde_json::to_value(&reparsed).unwrap().to_string();
+    assert_eq!(first, second);
+});
@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use vrc_oscquery::node::{validate_node, Access, OscNode};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(node) = serde_json::from_slice::<OscNode>(data) {
+        let _ = validate_node(&node);
+    }
+
+    // Arbitrary strings as a typetag on an otherwise valid method
+    if let Ok(typetag) = std::str::from_utf8(data) {
+        let _ = validate_node(&OscNode::new_method("/fuzz", Access::ReadWrite, typetag));
+    }
+});
@@ -0,0 +1,171 @@
+//! Common Log Format access log for `with_access_log_file`
+
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::{mpsc, oneshot};
+
+/// The log is renamed with a timestamp suffix once it would grow past this
+const MAX_LOG_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Lines queued before request handlers have to wait for the writer
+const LOG_BUFFER: usize = 1024;
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+enum Message {
+    Line(String),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Handle to the background task that owns the log file
+#[derive(Clone)]
+pub(crate) struct AccessLog {
+    sender: mpsc::Sender<Message>,
+}
+
+impl AccessLog {
+    /// Opens `path` for appending and starts the writer task
+    pub(crate) async fn open(path: &Path) -> std::io::Result<Self> {
+        let file = open_append(path).await?;
+        let size = file.metadata().await?.len();
+        let (sender, receiver) = mpsc::channel(LOG_BUFFER);
+        tokio::task::spawn(write_lines(path.to_path_buf(), file, size, receiver));
+        Ok(Self { sender })
+    }
+
+    pub(crate) async fn log(&self, line: String) {
+        // Only fails once the writer task is gone, and then there's nowhere to log to
+        self.sender.send(Message::Line(line)).await.ok();
+    }
+
+    /// Waits until every line logged so far has been written out
+    pub(crate) async fn flush(&self) {
+        let (done, flushed) = oneshot::channel();
+        if self.sender.send(Message::Flush(done)).await.is_ok() {
+            flushed.await.ok();
+        }
+    }
+}
+
+/// `host ident authuser [date] "request line" status bytes`, with a newline
+pub(crate) fn clf_line(
+    remote_ip: IpAddr,
+    time: SystemTime,
+    request_line: &str,
+    status: u16,
+    bytes: Option<u64>,
+) -> String {
+    let t = UtcTime::from(time);
+    let bytes = bytes.map_or("-".to_string(), |bytes| bytes.to_string());
+    format!(
+        "{} - - [{:02}/{}/{}:{:02}:{:02}:{:02} +0000] \"{}\" {} {}\n",
+        remote_ip,
+        t.day,
+        MONTHS[t.month as usize - 1],
+        t.year,
+        t.hour,
+        t.minute,
+        t.second,
+        request_line,
+        status,
+        bytes
+    )
+}
+
+async fn open_append(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path).await
+}
+
+async fn write_lines(
+    path: PathBuf,
+    file: File,
+    mut size: u64,
+    mut receiver: mpsc::Receiver<Message>,
+) {
+    let mut writer = BufWriter::new(file);
+    while let Some(message) = receiver.recv().await {
+        let result = match message {
+            Message::Line(line) => {
+                if size > 0 && size + line.len() as u64 > MAX_LOG_SIZE {
+                    match rotate(&path, &mut writer).await {
+                        Ok(()) => size = 0,
+                        Err(err) => log::warn!("Failed to rotate access log {}: {}", path.display(), err),
+                    }
+                }
+                size += line.len() as u64;
+                match writer.write_all(line.as_bytes()).await {
+                    // Batch up writes while requests keep coming in
+                    Ok(()) if receiver.is_empty() => writer.flush().await,
+                    other => other,
+                }
+            }
+            Message::Flush(done) => {
+                let result = writer.flush().await;
+                done.send(()).ok();
+                result
+            }
+        };
+        if let Err(err) = result {
+            log::warn!("Failed to write access log {}: {}", path.display(), err);
+        }
+    }
+    writer.flush().await.ok();
+}
+
+/// Moves the current file to `<path>.<YYYYMMDD-HHMMSS>` and starts a new one
+async fn rotate(path: &Path, writer: &mut BufWriter<File>) -> std::io::Result<()> {
+    writer.flush().await?;
+
+    let t = UtcTime::from(SystemTime::now());
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(format!(
+        ".{}{:02}{:02}-{:02}{:02}{:02}",
+        t.year, t.month, t.day, t.hour, t.minute, t.second
+    ));
+    tokio::fs::rename(path, &rotated).await?;
+
+    *writer = BufWriter::new(open_append(path).await?);
+    Ok(())
+}
+
+struct UtcTime {
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+}
+
+impl From<SystemTime> for UtcTime {
+    /// Howard Hinnant's `civil_from_days`, to avoid pulling in a date crate
+    fn from(time: SystemTime) -> Self {
+        let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()) as i64;
+        let (days, secs_of_day) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z - era * 146_097;
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + i64::from(month <= 2);
+
+        Self {
+            year,
+            month: month as u32,
+            day: day as u32,
+            hour: (secs_of_day / 3600) as u32,
+            minute: (secs_of_day % 3600 / 60) as u32,
+            second: (secs_of_day % 60) as u32,
+        }
+    }
+}
@@ -1,8 +1,18 @@
+use std::collections::HashMap;
+use std::fmt;
 use std::net::Ipv4Addr;
+use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
 
 use mdns_sd::{ServiceDaemon, ServiceEvent};
 use thiserror::Error;
+use tokio::sync::watch;
+
+use crate::http_client::OscQueryHttpClient;
+use crate::node::OscNode;
+
+/// Passed to [`discover_first_oscquery_service_matching`] predicates
+pub use mdns_sd::ResolvedService;
 
 #[derive(Debug, Clone)]
 pub struct DiscoveredOscQueryService {
@@ -12,6 +22,19 @@ pub struct DiscoveredOscQueryService {
     pub port: u16,
 }
 
+impl DiscoveredOscQueryService {
+    /// Base URL of the service's OSCQuery HTTP server
+    pub fn to_url(&self) -> String {
+        format!("http://{}:{}", self.addr_v4, self.port)
+    }
+}
+
+impl fmt::Display for DiscoveredOscQueryService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}:{}", self.instance_name, self.addr_v4, self.port)
+    }
+}
+
 /// Errors, errors, errors
 #[derive(Debug, Error)]
 pub enum OscQueryError {
@@ -26,13 +49,111 @@ pub enum OscQueryError {
 
     #[error("mDNS channel closed while waiting for VRChat OSCQuery service")]
     DiscoveryChannelClosed,
+
+    #[error("HTTP request to {url} failed: {reason}")]
+    Http { url: String, reason: String },
+
+    #[error("Invalid OSCQuery URL: {0}")]
+    InvalidUrl(String),
+
+    #[error("Node not found: {0}")]
+    NodeNotFound(String),
+
+    #[error("DNS lookup for {name} failed: {reason}")]
+    Dns { name: String, reason: String },
+}
+
+/// Retry settings for [`discover_vrchat_oscquery_with_config`]
+#[derive(Debug, Clone)]
+pub struct DiscoveryConfig {
+    retries: u32,
+    retry_backoff: Duration,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            retries: 0,
+            retry_backoff: Duration::from_millis(500),
+        }
+    }
 }
 
+impl DiscoveryConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of additional attempts after the first one times out
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Delay before the first retry, doubled after every further attempt
+    pub fn with_retry_backoff(mut self, backoff: Duration) -> Self {
+        self.retry_backoff = backoff;
+        self
+    }
+}
+
+/// Instance name prefix VRChat uses for its OSCQuery service
+pub const VRCHAT_SERVICE_PREFIX: &str = "VRChat-Client-";
+
 pub async fn discover_vrchat_oscquery(
     timeout: Duration,
+) -> Result<DiscoveredOscQueryService, OscQueryError> {
+    discover_vrchat_oscquery_with_prefix(VRCHAT_SERVICE_PREFIX, timeout).await
+}
+
+/// Like [`discover_vrchat_oscquery`], for when VRChat's instance names
+/// start with something other than [`VRCHAT_SERVICE_PREFIX`]
+pub async fn discover_vrchat_oscquery_with_prefix(
+    prefix: &str,
+    timeout: Duration,
+) -> Result<DiscoveredOscQueryService, OscQueryError> {
+    discover_first_oscquery_service_matching(|info| info.fullname.starts_with(prefix), timeout).await
+}
+
+/// First `_oscjson._tcp` service for which `predicate` returns true
+pub async fn discover_first_oscquery_service_matching(
+    predicate: impl Fn(&ResolvedService) -> bool,
+    timeout: Duration,
+) -> Result<DiscoveredOscQueryService, OscQueryError> {
+    discover_first("_oscjson._tcp.local.", timeout, predicate).await
+}
+
+/// Discover VRChat, retrying with exponential backoff if it isn't found
+///
+/// Each retry resets the per-attempt `timeout` and uses a fresh mDNS daemon,
+/// since the previous one may have missed announcements made while VRChat
+/// was still starting up. Only timeouts are retried.
+pub async fn discover_vrchat_oscquery_with_config(
+    config: DiscoveryConfig,
+    timeout: Duration,
+) -> Result<DiscoveredOscQueryService, OscQueryError> {
+    let mut backoff = config.retry_backoff;
+    let mut attempt = 0;
+
+    loop {
+        match discover_first("_oscjson._tcp.local.", timeout, is_vrchat_service).await {
+            Err(OscQueryError::DiscoveryTimeout) if attempt < config.retries => {
+                attempt += 1;
+                tokio::time::sleep(backoff).await;
+                backoff = backoff.saturating_mul(2);
+            }
+            result => return result,
+        }
+    }
+}
+
+async fn discover_first(
+    service_type: &str,
+    timeout: Duration,
+    accept: impl Fn(&ResolvedService) -> bool,
 ) -> Result<DiscoveredOscQueryService, OscQueryError> {
     let mdns = ServiceDaemon::new()?;
-    let receiver = mdns.browse("_oscjson._tcp.local.")?;
+    let receiver = mdns.browse(service_type)?;
 
     let deadline = Instant::now() + timeout;
 
@@ -60,32 +181,375 @@ pub async fn discover_vrchat_oscquery(
             }
         };
 
-        match event {
-            ServiceEvent::ServiceResolved(info) => {
-                if info.ty_domain == "_oscjson._tcp.local."
-                    && info.fullname.starts_with("VRChat-Client-")
-                {
-                    let v4_addrs = info.get_addresses_v4();
-                    let addr = v4_addrs
-                        .iter()
-                        .next()
-                        .cloned()
-                        .unwrap_or(Ipv4Addr::LOCALHOST);
-
-                    let out = DiscoveredOscQueryService {
-                        instance_name: info.fullname.clone(),
-                        host_name: info.host.clone(),
-                        addr_v4: addr,
-                        port: info.port,
-                    };
-
-                    mdns.shutdown().ok();
-                    return Ok(out);
+        log_mdns_event(&event);
+        if let ServiceEvent::ServiceResolved(info) = event {
+            if accept(&info) {
+                mdns.shutdown().ok();
+                return Ok(to_discovered(&info));
+            }
+        }
+    }
+}
+
+/// Collect every VRChat instance that resolves within `timeout`
+///
+/// Unlike [`discover_vrchat_oscquery`] this always waits out the full
+/// window. Instances are deduplicated by `instance_name`; an empty result
+/// is not an error.
+pub async fn discover_all_vrchat_instances(
+    timeout: Duration,
+) -> Result<Vec<DiscoveredOscQueryService>, OscQueryError> {
+    collect_services("_oscjson._tcp.local.", timeout, is_vrchat_service).await
+}
+
+/// Collect every service of `service_type` that resolves within `timeout`
+///
+/// For OSCQuery services other than VRChat, e.g. `_oscjson._tcp.local.` or
+/// `_osc._udp.local.`. Results are deduplicated by `instance_name`.
+pub async fn discover_by_service_type(
+    service_type: &str,
+    timeout: Duration,
+) -> Result<Vec<DiscoveredOscQueryService>, OscQueryError> {
+    collect_services(service_type, timeout, |_| true).await
+}
+
+async fn collect_services(
+    service_type: &str,
+    timeout: Duration,
+    accept: impl Fn(&ResolvedService) -> bool,
+) -> Result<Vec<DiscoveredOscQueryService>, OscQueryError> {
+    let mdns = ServiceDaemon::new()?;
+    let receiver = mdns.browse(service_type)?;
+
+    let deadline = Instant::now() + timeout;
+    let mut found: Vec<DiscoveredOscQueryService> = Vec::new();
+
+    loop {
+        let remaining = match deadline.checked_duration_since(Instant::now()) {
+            Some(d) if !d.is_zero() => d,
+            _ => break,
+        };
+
+        let event = match tokio::time::timeout(remaining, receiver.recv_async()).await {
+            Ok(Ok(ev)) => ev,
+            Ok(Err(_)) => {
+                mdns.shutdown().ok();
+                return Err(OscQueryError::DiscoveryChannelClosed);
+            }
+            Err(_) => break,
+        };
+
+        log_mdns_event(&event);
+        if let ServiceEvent::ServiceResolved(info) = event {
+            if accept(&info) && !found.iter().any(|s| s.instance_name == info.fullname) {
+                found.push(to_discovered(&info));
+            }
+        }
+    }
+
+    mdns.shutdown().ok();
+    Ok(found)
+}
+
+/// Keeps track of the OSCQuery services currently on the network
+///
+/// Browses `_oscjson._tcp.local.` in the background for as long as the
+/// watcher is alive. Has to be created inside a tokio runtime.
+pub struct OscQueryWatcher {
+    mdns: ServiceDaemon,
+    services: Arc<RwLock<HashMap<String, DiscoveredOscQueryService>>>,
+    changes: watch::Receiver<()>,
+}
+
+impl OscQueryWatcher {
+    pub fn new() -> Result<Self, OscQueryError> {
+        let mdns = ServiceDaemon::new()?;
+        let receiver = mdns.browse("_oscjson._tcp.local.")?;
+        let services = Arc::new(RwLock::new(HashMap::new()));
+        let (changed, changes) = watch::channel(());
+
+        // The channel closes when the daemon is shut down on drop
+        let known = services.clone();
+        tokio::task::spawn(async move {
+            while let Ok(event) = receiver.recv_async().await {
+                log_mdns_event(&event);
+                match event {
+                    ServiceEvent::ServiceResolved(info) => {
+                        known
+                            .write()
+                            .unwrap()
+                            .insert(info.fullname.clone(), to_discovered(&info));
+                    }
+                    ServiceEvent::ServiceRemoved(_, fullname) => {
+                        known.write().unwrap().remove(&fullname);
+                    }
+                    _ => continue,
                 }
+                changed.send_replace(());
             }
-            _ => {
-                // Ignore other events.
+        });
+
+        Ok(Self {
+            mdns,
+            services,
+            changes,
+        })
+    }
+
+    /// Services known right now, sorted by instance name
+    ///
+    /// Doesn't wait for anything, so right after [`Self::new`] this is
+    /// usually still empty.
+    pub fn list_active_services(&self) -> Vec<DiscoveredOscQueryService> {
+        let mut services: Vec<_> = self.services.read().unwrap().values().cloned().collect();
+        services.sort_by(|a, b| a.instance_name.cmp(&b.instance_name));
+        services
+    }
+
+    /// Marks a service as known before mDNS has reported it
+    fn insert(&self, service: DiscoveredOscQueryService) {
+        self.services
+            .write()
+            .unwrap()
+            .insert(service.instance_name.clone(), service);
+    }
+
+    /// Fires whenever a service is resolved or removed
+    fn subscribe(&self) -> watch::Receiver<()> {
+        self.changes.clone()
+    }
+}
+
+impl Drop for OscQueryWatcher {
+    fn drop(&mut self) {
+        self.mdns.shutdown().ok();
+    }
+}
+
+/// Long-lived connection to one OSCQuery service
+///
+/// Keeps an [`OscQueryWatcher`] running so it notices when the service goes
+/// away and comes back, e.g. when VRChat is restarted. VRChat picks a new
+/// instance name and port on every launch, so for VRChat services any
+/// VRChat instance counts as the same service; everything else has to come
+/// back under its old instance name.
+pub struct OscQueryClient {
+    watcher: OscQueryWatcher,
+    service: RwLock<DiscoveredOscQueryService>,
+}
+
+impl OscQueryClient {
+    /// Starts watching and checks that `service` answers `HOST_INFO`
+    pub async fn connect(service: DiscoveredOscQueryService) -> Result<Self, OscQueryError> {
+        OscQueryHttpClient::for_service(&service)
+            .get_host_info()
+            .await?;
+
+        let watcher = OscQueryWatcher::new()?;
+        // mDNS hasn't reported anything yet, but the service is clearly up
+        watcher.insert(service.clone());
+
+        Ok(Self {
+            watcher,
+            service: RwLock::new(service),
+        })
+    }
+
+    /// The service requests currently go to
+    pub fn service(&self) -> DiscoveredOscQueryService {
+        self.service.read().unwrap().clone()
+    }
+
+    pub async fn fetch_tree(&self) -> Result<OscNode, OscQueryError> {
+        OscQueryHttpClient::for_service(&self.service())
+            .get_tree()
+            .await
+    }
+
+    /// Waits until the service has disappeared from mDNS and come back
+    ///
+    /// Returns right away if it is already gone and something matching has
+    /// shown up since. Afterwards [`Self::service`] and [`Self::fetch_tree`]
+    /// use the new address and port.
+    pub async fn wait_for_reconnect(&self) {
+        let mut changes = self.watcher.subscribe();
+        let mut lost = false;
+
+        loop {
+            let current = self.service();
+            let active = self.watcher.list_active_services();
+
+            if !lost {
+                lost = !active
+                    .iter()
+                    .any(|s| s.instance_name == current.instance_name);
             }
+            if lost {
+                if let Some(service) = active.into_iter().find(|s| same_service(&current, s)) {
+                    log::debug!("OSCQuery service back as {}", service);
+                    *self.service.write().unwrap() = service;
+                    return;
+                }
+            }
+
+            if changes.changed().await.is_err() {
+                // Watcher task is gone, nothing will ever change again
+                return std::future::pending().await;
+            }
+        }
+    }
+}
+
+fn same_service(old: &DiscoveredOscQueryService, new: &DiscoveredOscQueryService) -> bool {
+    old.instance_name == new.instance_name
+        || (old.instance_name.starts_with(VRCHAT_SERVICE_PREFIX)
+            && new.instance_name.starts_with(VRCHAT_SERVICE_PREFIX))
+}
+
+/// Debug log of every browse event, for when discovery finds nothing
+///
+/// Goes through `tracing` with the `tracing` feature and `log` otherwise.
+fn log_mdns_event(event: &ServiceEvent) {
+    let (what, service_type, instance) = match event {
+        ServiceEvent::SearchStarted(ty) => ("search started", ty.as_str(), ""),
+        ServiceEvent::ServiceFound(ty, fullname) => ("service found", ty.as_str(), fullname.as_str()),
+        ServiceEvent::ServiceResolved(info) => {
+            ("service resolved", info.ty_domain.as_str(), info.fullname.as_str())
         }
+        ServiceEvent::ServiceRemoved(ty, fullname) => {
+            ("service removed", ty.as_str(), fullname.as_str())
+        }
+        ServiceEvent::SearchStopped(ty) => ("search stopped", ty.as_str(), ""),
+        _ => return,
+    };
+
+    #[cfg(feature = "tracing")]
+    tracing::debug!(service_type, instance, "mDNS {}", what);
+    #[cfg(not(feature = "tracing"))]
+    log::debug!(
+        "mDNS {}: service_type={} instance={}",
+        what,
+        service_type,
+        instance
+    );
+}
+
+fn is_vrchat_service(info: &ResolvedService) -> bool {
+    info.ty_domain == "_oscjson._tcp.local." && info.fullname.starts_with(VRCHAT_SERVICE_PREFIX)
+}
+
+fn to_discovered(info: &ResolvedService) -> DiscoveredOscQueryService {
+    let v4_addrs = info.get_addresses_v4();
+    let addr = v4_addrs
+        .iter()
+        .next()
+        .cloned()
+        .unwrap_or(Ipv4Addr::LOCALHOST);
+
+    DiscoveredOscQueryService {
+        instance_name: info.fullname.clone(),
+        host_name: info.host.clone(),
+        addr_v4: addr,
+        port: info.port,
+    }
+}
+
+/// Discover OSCQuery services through a unicast DNS-SD server (RFC 6763)
+///
+/// Looks up the `_oscjson._tcp.{domain}` PTR records on `dns_server` and
+/// resolves each instance through its SRV and A records. Unlike mDNS this
+/// works across subnets, but someone has to publish the records. Instances
+/// without an IPv4 address, or whose SRV or A lookup fails, are logged and
+/// skipped. If `timeout` runs out, the instances resolved so far are
+/// returned, or [`OscQueryError::DiscoveryTimeout`] if there are none.
+#[cfg(feature = "unicast-dns")]
+pub async fn discover_oscquery_service_unicast(
+    dns_server: std::net::SocketAddr,
+    domain: &str,
+    timeout: Duration,
+) -> Result<Vec<DiscoveredOscQueryService>, OscQueryError> {
+    use hickory_resolver::config::{NameServerConfig, Protocol, ResolverConfig, ResolverOpts};
+    use hickory_resolver::proto::rr::RecordType;
+    use hickory_resolver::TokioAsyncResolver;
+
+    let mut config = ResolverConfig::new();
+    config.add_name_server(NameServerConfig::new(dns_server, Protocol::Udp));
+    let mut opts = ResolverOpts::default();
+    opts.timeout = timeout;
+    let resolver = TokioAsyncResolver::tokio(config, opts);
+
+    let dns_error = |name: &str, err: hickory_resolver::error::ResolveError| OscQueryError::Dns {
+        name: name.to_string(),
+        reason: err.to_string(),
+    };
+
+    let mut found = Vec::new();
+    let lookup = async {
+        let service_name = format!("_oscjson._tcp.{}.", domain.trim_end_matches('.'));
+        let ptrs = resolver
+            .lookup(service_name.as_str(), RecordType::PTR)
+            .await
+            .map_err(|e| dns_error(&service_name, e))?;
+
+        for instance in ptrs.iter().filter_map(|rdata| rdata.as_ptr()) {
+            let instance_name = instance.to_string();
+            let srvs = match resolver.srv_lookup(instance.0.clone()).await {
+                Ok(srvs) => srvs,
+                Err(err) => {
+                    log::warn!("Skipping OSCQuery instance {}: SRV lookup failed: {}", instance_name, err);
+                    continue;
+                }
+            };
+
+            for srv in srvs.iter() {
+                let host_name = srv.target().to_string();
+                let addrs = match resolver.ipv4_lookup(srv.target().clone()).await {
+                    Ok(addrs) => addrs,
+                    Err(err) => {
+                        log::warn!(
+                            "Skipping OSCQuery instance {}: A lookup for {} failed: {}",
+                            instance_name,
+                            host_name,
+                            err
+                        );
+                        continue;
+                    }
+                };
+
+                if let Some(addr) = addrs.iter().next() {
+                    found.push(DiscoveredOscQueryService {
+                        instance_name: instance_name.clone(),
+                        host_name,
+                        addr_v4: addr.0,
+                        port: srv.port(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    };
+
+    match tokio::time::timeout(timeout, lookup).await {
+        Ok(result) => result.map(|()| found),
+        // Keep the instances that resolved before time ran out
+        Err(_) if !found.is_empty() => Ok(found),
+        Err(_) => Err(OscQueryError::DiscoveryTimeout),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_and_url() {
+        let service = DiscoveredOscQueryService {
+            instance_name: "VRChat-Client-123456".to_string(),
+            host_name: "desktop.local.".to_string(),
+            addr_v4: Ipv4Addr::new(192, 168, 1, 20),
+            port: 54321,
+        };
+        assert_eq!(service.to_string(), "VRChat-Client-123456 at 192.168.1.20:54321");
+        assert_eq!(service.to_url(), "http://192.168.1.20:54321");
     }
 }
@@ -0,0 +1,18 @@
+use crate::client;
+use crate::server::OscQueryServerError;
+
+/// Any error this crate can return
+///
+/// For code that both serves and discovers. Match on the inner
+/// [`OscQueryServerError`] or [`client::OscQueryError`] for the details.
+#[derive(Debug, thiserror::Error)]
+pub enum OscQueryError {
+    #[error(transparent)]
+    Server(#[from] OscQueryServerError),
+
+    #[error(transparent)]
+    Client(#[from] client::OscQueryError),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
@@ -0,0 +1,168 @@
+use std::fmt::Write;
+
+use crate::node::{Access, OscNode, OscNodeError};
+
+const CSV_HEADER: &str = "Path,Type,Access,Description,Range";
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CsvParseError {
+    #[error("Expected header {CSV_HEADER:?}, found {0:?}")]
+    InvalidHeader(String),
+
+    #[error("Line {line}: expected 5 columns, found {found}")]
+    WrongColumnCount { line: usize, found: usize },
+
+    #[error("Line {line}: unknown access {value:?}")]
+    InvalidAccess { line: usize, value: String },
+
+    #[error("Line {line}: unterminated quoted field")]
+    UnterminatedQuote { line: usize },
+
+    #[error("Line {line}: {source}")]
+    InvalidMethod {
+        line: usize,
+        #[source]
+        source: OscNodeError,
+    },
+}
+
+/// One row per method with columns `Path,Type,Access,Description,Range`
+///
+/// Nodes don't carry a description or range yet, so those columns are
+/// left empty for now. Access is written as `None`, `Read`, `Write` or
+/// `ReadWrite`.
+pub fn export_to_csv(root: &OscNode) -> String {
+    let mut methods = OscNode::find(root, |node| node.typetag.is_some());
+    methods.sort_by(|a, b| a.full_path.cmp(&b.full_path));
+
+    let mut out = String::new();
+    writeln!(out, "{}", CSV_HEADER).unwrap();
+    for node in methods {
+        writeln!(
+            out,
+            "{},{},{},,",
+            csv_field(&node.full_path),
+            csv_field(node.typetag.as_deref().unwrap_or_default()),
+            node.access.map(|access| access.to_string()).unwrap_or_default(),
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// Rebuild a tree from [`export_to_csv`] output
+///
+/// Rows with an empty `Type` become containers. `Description` and `Range`
+/// are accepted but ignored.
+pub fn from_csv(s: &str) -> Result<OscNode, CsvParseError> {
+    let mut lines = s.lines().enumerate();
+    match lines.next() {
+        Some((_, header)) if header.trim_end() == CSV_HEADER => {}
+        Some((_, header)) => return Err(CsvParseError::InvalidHeader(header.to_string())),
+        None => return Err(CsvParseError::InvalidHeader(String::new())),
+    }
+
+    let mut root = OscNode::new_container("/");
+    for (idx, line) in lines {
+        let line_no = idx + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let fields = split_csv_line(line)
+            .ok_or(CsvParseError::UnterminatedQuote { line: line_no })?;
+        if fields.len() != 5 {
+            return Err(CsvParseError::WrongColumnCount {
+                line: line_no,
+                found: fields.len(),
+            });
+        }
+
+        let (path, typetag, access) = (&fields[0], &fields[1], &fields[2]);
+        if typetag.is_empty() {
+            OscNode::ensure_path(&mut root, path);
+            continue;
+        }
+
+        let access = parse_access(access).ok_or_else(|| CsvParseError::InvalidAccess {
+            line: line_no,
+            value: access.clone(),
+        })?;
+        OscNode::try_add_method(&mut root, path, access, typetag)
+            .map_err(|source| CsvParseError::InvalidMethod { line: line_no, source })?;
+    }
+    Ok(root)
+}
+
+fn parse_access(value: &str) -> Option<Access> {
+    match value {
+        "None" => Some(Access::None),
+        "Read" => Some(Access::Read),
+        "Write" => Some(Access::Write),
+        "ReadWrite" | "" => Some(Access::ReadWrite),
+        _ => None,
+    }
+}
+
+/// Quote a field if it contains a comma, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Split one CSV line, returning `None` on an unterminated quote
+fn split_csv_line(line: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+
+    if quoted {
+        return None;
+    }
+    fields.push(field);
+    Some(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_csv_rejects_invalid_methods() {
+        let csv = format!("{}\n/a,f,Read,,\n/a b,f,Read,,\n", CSV_HEADER);
+        assert_eq!(
+            from_csv(&csv).unwrap_err(),
+            CsvParseError::InvalidMethod {
+                line: 3,
+                source: OscNodeError::InvalidOscPath("/a b".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let mut root = OscNode::new_container("/");
+        OscNode::add_method(&mut root, "/avatar/change", Access::Read, "s");
+        OscNode::add_method(&mut root, "/input/Jump", Access::Write, "i");
+
+        let parsed = from_csv(&export_to_csv(&root)).unwrap();
+        assert_eq!(export_to_csv(&parsed), export_to_csv(&root));
+    }
+}
@@ -0,0 +1,214 @@
+use std::time::Duration;
+
+use crate::client::{DiscoveredOscQueryService, OscQueryError};
+use crate::node::OscNode;
+use crate::server::HostInfo;
+
+/// Reads the node tree of a remote OSCQuery server over HTTP
+///
+/// With the `reqwest` feature this uses a pooled `reqwest::Client`.
+/// Without it, every request opens a fresh hyper connection that is
+/// closed once the response has been read. Either way a single redirect
+/// is followed, and a request that takes longer than the timeout fails.
+pub struct OscQueryHttpClient {
+    base_url: String,
+    timeout: Duration,
+    #[cfg(feature = "reqwest")]
+    client: reqwest::Client,
+}
+
+/// How long a request, redirect included, may take by default
+pub const DEFAULT_HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+impl OscQueryHttpClient {
+    /// `base_url` is the server root, e.g. `http://127.0.0.1:9001`
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            timeout: DEFAULT_HTTP_TIMEOUT,
+            #[cfg(feature = "reqwest")]
+            client: reqwest_client(DEFAULT_HTTP_TIMEOUT),
+        }
+    }
+
+    /// Fail requests that take longer than `timeout` to connect and answer
+    ///
+    /// Defaults to [`DEFAULT_HTTP_TIMEOUT`].
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        #[cfg(feature = "reqwest")]
+        {
+            self.client = reqwest_client(timeout);
+        }
+        self
+    }
+
+    pub fn for_service(service: &DiscoveredOscQueryService) -> Self {
+        Self::new(service.to_url())
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub async fn get_host_info(&self) -> Result<HostInfo, OscQueryError> {
+        let url = format!("{}/?HOST_INFO", self.base_url);
+        let (status, body) = self.get(&url).await?;
+
+        match status {
+            200..=299 => Ok(serde_json::from_slice(&body)?),
+            _ => Err(OscQueryError::Http {
+                url,
+                reason: format!("unexpected status {}", status),
+            }),
+        }
+    }
+
+    pub async fn get_tree(&self) -> Result<OscNode, OscQueryError> {
+        self.get_node("/").await
+    }
+
+    /// Node at `path`, which may leave out the leading `/`
+    pub async fn get_node(&self, path: &str) -> Result<OscNode, OscQueryError> {
+        let path = if path.starts_with('/') { path.to_string() } else { format!("/{}", path) };
+        let url = format!("{}{}", self.base_url, path);
+        let (status, body) = self.get(&url).await?;
+
+        match status {
+            404 => Err(OscQueryError::NodeNotFound(path)),
+            200..=299 => Ok(serde_json::from_slice(&body)?),
+            _ => Err(OscQueryError::Http {
+                url,
+                reason: format!("unexpected status {}", status),
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "reqwest")]
+fn reqwest_client(timeout: Duration) -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::limited(1))
+        .connect_timeout(timeout)
+        .timeout(timeout)
+        .build()
+        .unwrap_or_default()
+}
+
+#[cfg(feature = "reqwest")]
+impl OscQueryHttpClient {
+    async fn get(&self, url: &str) -> Result<(u16, Vec<u8>), OscQueryError> {
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| http_error(url, e))?;
+        let status = response.status().as_u16();
+        let body = response.bytes().await.map_err(|e| http_error(url, e))?;
+        Ok((status, body.to_vec()))
+    }
+}
+
+#[cfg(not(feature = "reqwest"))]
+impl OscQueryHttpClient {
+    async fn get(&self, url: &str) -> Result<(u16, Vec<u8>), OscQueryError> {
+        let request = async {
+            let (status, location, body) = self.get_once(url).await?;
+            match (status, location) {
+                (300..=399, Some(location)) => {
+                    let target = redirect_target(url, &location);
+                    let (status, _, body) = self.get_once(&target).await?;
+                    Ok((status, body))
+                }
+                _ => Ok((status, body)),
+            }
+        };
+        tokio::time::timeout(self.timeout, request)
+            .await
+            .map_err(|_| http_error(url, format!("timed out after {:?}", self.timeout)))?
+    }
+
+    /// One request, returning the status, `Location` header and body
+    async fn get_once(&self, url: &str) -> Result<(u16, Option<String>, Vec<u8>), OscQueryError> {
+        use http_body_util::{BodyExt, Empty};
+        use hyper::body::Bytes;
+        use hyper::header::{CONNECTION, HOST, LOCATION};
+        use hyper::{Request, Uri};
+        use hyper_util::rt::TokioIo;
+
+        let uri: Uri = url
+            .parse()
+            .map_err(|_| OscQueryError::InvalidUrl(url.to_string()))?;
+        if uri.scheme_str() != Some("http") {
+            return Err(OscQueryError::InvalidUrl(url.to_string()));
+        }
+        let authority = uri
+            .authority()
+            .ok_or_else(|| OscQueryError::InvalidUrl(url.to_string()))?
+            .clone();
+        let path_and_query = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+
+        let port = authority.port_u16().unwrap_or(80);
+        let stream = tokio::net::TcpStream::connect((authority.host(), port))
+            .await
+            .map_err(|e| http_error(url, e))?;
+        let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+            .await
+            .map_err(|e| http_error(url, e))?;
+        tokio::task::spawn(async move {
+            if let Err(err) = conn.await {
+                log::debug!("OSCQuery HTTP connection error: {:?}", err);
+            }
+        });
+
+        let request = Request::get(path_and_query)
+            .header(HOST, authority.as_str())
+            .header(CONNECTION, "close")
+            .body(Empty::<Bytes>::new())
+            .map_err(|_| OscQueryError::InvalidUrl(url.to_string()))?;
+
+        let response = sender
+            .send_request(request)
+            .await
+            .map_err(|e| http_error(url, e))?;
+        let status = response.status().as_u16();
+        let location = response
+            .headers()
+            .get(LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .map(|location| location.to_string());
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .map_err(|e| http_error(url, e))?
+            .to_bytes();
+        Ok((status, location, body.to_vec()))
+    }
+}
+
+/// Resolves a `Location` header against the URL that returned it
+#[cfg(not(feature = "reqwest"))]
+fn redirect_target(url: &str, location: &str) -> String {
+    if !location.starts_with('/') {
+        return location.to_string();
+    }
+    // Keep `scheme://authority` and swap in the new path
+    let origin_end = url
+        .find("://")
+        .and_then(|scheme_end| url[scheme_end + 3..].find('/').map(|idx| scheme_end + 3 + idx))
+        .unwrap_or(url.len());
+    format!("{}{}", &url[..origin_end], location)
+}
+
+fn http_error(url: &str, err: impl std::fmt::Display) -> OscQueryError {
+    OscQueryError::Http {
+        url: url.to_string(),
+        reason: err.to_string(),
+    }
+}
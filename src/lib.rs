@@ -1,3 +1,11 @@
 pub mod node;
 pub mod server;
 pub mod client;
+pub mod http_client;
+pub mod export;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod error;
+mod access_log;
+
+pub use error::OscQueryError;
@@ -1,43 +1,300 @@
+use std::cell::Cell;
 use std::collections::HashMap;
-use serde::Serialize;
-use serde_repr::Serialize_repr;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use serde::de::{self, DeserializeSeed, MapAccess, Visitor};
+use serde::ser::{SerializeMap, SerializeStruct};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_repr::{Deserialize_repr, Serialize_repr};
 
-#[derive(Debug, Clone, Serialize)]
-pub(crate) struct OscNode {
+use crate::client::OscQueryError;
+use crate::http_client::OscQueryHttpClient;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OscNode {
     #[serde(rename = "FULL_PATH")]
     pub full_path: String,
 
-    #[serde(rename = "ACCESS", skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "ACCESS")]
     pub access: Option<Access>,
 
     /// TYPE: standard OSC typetag string, e.g. "f", "i", "s" etc.
-    #[serde(rename = "TYPE", skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "TYPE")]
     pub typetag: Option<String>,
 
-    #[serde(rename = "VALUE", skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "VALUE")]
     pub value: Option<serde_json::Value>,
 
-    #[serde(rename = "CONTENTS", skip_serializing_if = "HashMap::is_empty")]
+    #[serde(rename = "CONTENTS", default)]
     pub contents: HashMap<String, OscNode>,
+
+    /// OVERLOADS: alternative type signatures accepted at this path
+    #[serde(rename = "OVERLOADS", default)]
+    pub overloads: Option<Vec<OscOverload>>,
+
+    /// EXTENDED_TYPE: application-specific meaning beyond the typetag, e.g. "color.rgba"
+    #[serde(rename = "EXTENDED_TYPE", default)]
+    pub extended_type: Option<String>,
+
+    /// INHERITS: path of a node whose type information this one shares
+    #[serde(rename = "INHERITS", default)]
+    pub inherits: Option<String>,
+
+    /// Locked nodes refuse `VALUE` updates, see [`OscNode::lock`]. Never serialized.
+    #[serde(skip)]
+    pub locked: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct OscOverload {
+    #[serde(rename = "TYPE")]
+    pub typetag: String,
+
+    #[serde(rename = "DESCRIPTION", default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl Serialize for OscNode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        NodeAt {
+            node: self,
+            full_path: &self.full_path,
+            emit_path: true,
+            child_paths: true,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// A node serialized as if it sat at `full_path`
+///
+/// Only the outermost node uses its stored `FULL_PATH`. Below that the path
+/// is built from the `CONTENTS` keys on the way down, so the JSON matches
+/// the tree's shape even if stored paths went stale after moving nodes.
+/// That also makes the nested paths redundant, which the compact encodings
+/// use to leave them out.
+struct NodeAt<'a> {
+    node: &'a OscNode,
+    full_path: &'a str,
+    emit_path: bool,
+    /// Whether the nodes in `CONTENTS` emit their `FULL_PATH`
+    child_paths: bool,
+}
+
+/// `CONTENTS` of a node at `base`
+struct ContentsAt<'a> {
+    node: &'a OscNode,
+    base: &'a str,
+    child_paths: bool,
+}
+
+impl Serialize for ContentsAt<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let base = self.base.trim_end_matches('/');
+        let mut map = serializer.serialize_map(Some(self.node.contents.len()))?;
+        for (name, child) in &self.node.contents {
+            let full_path = format!("{}/{}", base, name);
+            let child = NodeAt {
+                node: child,
+                full_path: &full_path,
+                emit_path: self.child_paths,
+                child_paths: self.child_paths,
+            };
+            map.serialize_entry(name, &child)?;
+        }
+        map.end()
+    }
+}
+
+impl Serialize for NodeAt<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let node = self.node;
+
+        // Methods are leaves and never emit CONTENTS; containers keep the
+        // nested form once they have children
+        let emit_contents = !node.contents.is_empty();
+
+        let len = self.emit_path as usize
+            + node.access.is_some() as usize
+            + node.typetag.is_some() as usize
+            + node.value.is_some() as usize
+            + emit_contents as usize
+            + node.overloads.is_some() as usize
+            + node.extended_type.is_some() as usize
+            + node.inherits.is_some() as usize;
+
+        let mut state = serializer.serialize_struct("OscNode", len)?;
+        if self.emit_path {
+            state.serialize_field("FULL_PATH", self.full_path)?;
+        }
+        if let Some(access) = &node.access {
+            state.serialize_field("ACCESS", access)?;
+        }
+        if let Some(typetag) = &node.typetag {
+            state.serialize_field("TYPE", typetag)?;
+        }
+        if let Some(value) = &node.value {
+            state.serialize_field("VALUE", value)?;
+        }
+        if emit_contents {
+            let contents = ContentsAt {
+                node,
+                base: self.full_path,
+                child_paths: self.child_paths,
+            };
+            state.serialize_field("CONTENTS", &contents)?;
+        }
+        if let Some(overloads) = &node.overloads {
+            state.serialize_field("OVERLOADS", overloads)?;
+        }
+        if let Some(extended_type) = &node.extended_type {
+            state.serialize_field("EXTENDED_TYPE", extended_type)?;
+        }
+        if let Some(inherits) = &node.inherits {
+            state.serialize_field("INHERITS", inherits)?;
+        }
+        state.end()
+    }
+}
+
+/// Compares what gets served
+///
+/// `locked` is ignored, and so are the stored `FULL_PATH`s of everything
+/// below `self`: the JSON builds those from the `CONTENTS` keys.
+impl PartialEq for OscNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.full_path == other.full_path && self.same_served(other)
+    }
+}
+
+impl Eq for OscNode {}
+
+// HashMap isn't Hash, so children are hashed in key order. Skips the same
+// fields as PartialEq to stay consistent with it.
+impl Hash for OscNode {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.full_path.hash(state);
+        self.hash_served(state);
+    }
+}
+
+impl OscNode {
+    /// [`PartialEq`] without the node's own `FULL_PATH`
+    fn same_served(&self, other: &Self) -> bool {
+        self.access == other.access
+            && self.typetag == other.typetag
+            && self.value == other.value
+            && self.overloads == other.overloads
+            && self.extended_type == other.extended_type
+            && self.inherits == other.inherits
+            && self.contents.len() == other.contents.len()
+            && self.contents.iter().all(|(name, child)| {
+                other.contents.get(name).is_some_and(|theirs| child.same_served(theirs))
+            })
+    }
+
+    /// [`Hash`] without the node's own `FULL_PATH`
+    fn hash_served<H: Hasher>(&self, state: &mut H) {
+        self.access.hash(state);
+        self.typetag.hash(state);
+        self.value.hash(state);
+        self.overloads.hash(state);
+        self.extended_type.hash(state);
+        self.inherits.hash(state);
+
+        let mut children: Vec<_> = self.contents.iter().collect();
+        children.sort_by(|a, b| a.0.cmp(b.0));
+        children.len().hash(state);
+        for (name, child) in children {
+            name.hash(state);
+            child.hash_served(state);
+        }
+    }
+}
+
+/// Indented tree like the Unix `tree` command, children sorted by name
+///
+/// ```text
+/// /
+/// └── avatar (None)
+///     └── parameters (None)
+///         └── GestureLeft i (ReadWrite) = [3]
+/// ```
+impl fmt::Display for OscNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.full_path)?;
+        fmt_children(self, "", f)
+    }
+}
+
+fn fmt_children(node: &OscNode, indent: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut children: Vec<_> = node.contents.iter().collect();
+    children.sort_by(|a, b| a.0.cmp(b.0));
+
+    let last = children.len().saturating_sub(1);
+    for (idx, (name, child)) in children.into_iter().enumerate() {
+        let (branch, next_indent) = if idx == last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        write!(f, "\n{}{}{}", indent, branch, name)?;
+        if let Some(typetag) = &child.typetag {
+            write!(f, " {}", typetag)?;
+        }
+        if let Some(access) = child.access {
+            write!(f, " ({})", access)?;
+        }
+        if let Some(value) = &child.value {
+            write!(f, " = {}", value)?;
+        }
+        fmt_children(child, &format!("{}{}", indent, next_indent), f)?;
+    }
+    Ok(())
 }
 
 #[repr(u8)]
-#[derive(Debug, Clone, Copy, Serialize_repr)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize_repr, Deserialize_repr)]
 pub enum Access {
     None = 0,
     Read = 1,
     Write = 2,
+    #[default]
     ReadWrite = 3,
 }
 
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Access::None => "None",
+            Access::Read => "Read",
+            Access::Write => "Write",
+            Access::ReadWrite => "ReadWrite",
+        })
+    }
+}
+
 impl OscNode {
     pub fn new_container(full_path: &str) -> Self {
+        Self::new_container_with_access(full_path, Access::None)
+    }
+
+    /// Container with an access other than the spec's `None`
+    ///
+    /// Some implementations mark containers `Read` when the container's own
+    /// properties can be queried. [`validate_access_consistency`] flags
+    /// these, see [`validate_access_consistency_allowing`].
+    pub fn new_container_with_access(full_path: &str, access: Access) -> Self {
         Self {
             full_path: full_path.to_string(),
-            access: Some(Access::None),
+            access: Some(access),
             typetag: None,
             value: None,
             contents: HashMap::new(),
+            overloads: None,
+            extended_type: None,
+            inherits: None,
+            locked: false,
         }
     }
 
@@ -48,42 +305,1401 @@ impl OscNode {
             typetag: Some(typetag.to_string()),
             value: None,
             contents: HashMap::new(),
+            overloads: None,
+            extended_type: None,
+            inherits: None,
+            locked: false,
         }
     }
 
-    pub fn ensure_path<'a>(root: &'a mut OscNode, path: &str) -> &'a mut OscNode {
-        if path == "/" {
-            return root;
+    /// Like [`Self::new_method`], but rejects bad paths and empty or
+    /// malformed typetags
+    ///
+    /// The path must pass [`path_components`] and [`validate_osc_path`],
+    /// so `/a//b`, `/a b` and `/a/*` are all refused, as is `/` itself.
+    pub fn try_new_method(full_path: &str, access: Access, typetag: &str) -> Result<Self, OscNodeError> {
+        match path_components(full_path) {
+            Ok(components) if !components.is_empty() => {}
+            _ => return Err(OscNodeError::InvalidMethodPath(full_path.to_string())),
         }
+        validate_osc_path(full_path)?;
+        if !is_valid_typetag(typetag) {
+            return Err(OscNodeError::InvalidTypetag(typetag.to_string()));
+        }
+        Ok(Self::new_method(full_path, access, typetag))
+    }
 
-        let mut parts = path.trim_matches('/').split('/');
-        let mut current = root;
+    /// Method that can only be read, e.g. a value this app reports
+    pub fn new_readable_method(full_path: &str, typetag: &str) -> Self {
+        Self::new_method(full_path, Access::Read, typetag)
+    }
+
+    /// Method that can only be written, e.g. a parameter this app receives
+    pub fn new_writable_method(full_path: &str, typetag: &str) -> Self {
+        Self::new_method(full_path, Access::Write, typetag)
+    }
+
+    /// Fetch the whole tree of a running OSCQuery server, e.g. `http://127.0.0.1:9001`
+    pub async fn from_oscquery_url(base_url: &str) -> Result<OscNode, OscQueryError> {
+        Self::from_oscquery_path(base_url, "/").await
+    }
+
+    /// Fetch just the subtree at `path`, for building up a large tree bit by bit
+    ///
+    /// Errors always carry the URL that was requested.
+    pub async fn from_oscquery_path(base_url: &str, path: &str) -> Result<OscNode, OscQueryError> {
+        let client = OscQueryHttpClient::new(base_url);
+        client.get_node(path).await.map_err(|err| match err {
+            OscQueryError::Http { .. } => err,
+            other => OscQueryError::Http {
+                url: format!("{}{}", client.base_url(), path),
+                reason: other.to_string(),
+            },
+        })
+    }
+
+    /// Parse a node tree from OSCQuery JSON without any limits
+    ///
+    /// Use [`Self::from_json_str_with_options`] for JSON from untrusted servers.
+    pub fn from_json_str(s: &str) -> Result<OscNode, serde_json::Error> {
+        serde_json::from_str(s)
+    }
+
+    /// Parse a node tree, giving up as soon as it exceeds `opts`
+    ///
+    /// Nodes are counted while the JSON is read, so an oversized tree is
+    /// rejected before it's fully in memory. Depth counts like
+    /// [`Self::max_depth`], with the root at 0.
+    pub fn from_json_str_with_options(
+        s: &str,
+        opts: DeserializeOptions,
+    ) -> Result<OscNode, OscNodeDeserializeError> {
+        let limits = Limits {
+            opts,
+            count: Cell::new(0),
+            exceeded: Cell::new(None),
+        };
+        let mut de = serde_json::Deserializer::from_str(s);
+        let value = match (NodeSeed { limits: &limits, depth: 0 }).deserialize(&mut de) {
+            Ok(value) => value,
+            Err(err) => return Err(limits.exceeded.take().unwrap_or(err.into())),
+        };
+        de.end()?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Compact MessagePack encoding with the same field names as the JSON
+    ///
+    /// Not part of OSCQuery, which is JSON only; meant for IPC between
+    /// processes that both use this crate. Only the outermost node carries
+    /// its `FULL_PATH`: the others follow from the `CONTENTS` keys and are
+    /// filled back in by [`Self::from_msgpack`].
+    #[cfg(feature = "msgpack")]
+    pub fn to_msgpack(node: &OscNode) -> Result<Vec<u8>, rmp_serde::encode::Error> {
+        let compact = NodeAt {
+            node,
+            full_path: &node.full_path,
+            emit_path: true,
+            child_paths: false,
+        };
+        // Named, since the positional struct encoding can't handle the
+        // fields that are left out when unset
+        rmp_serde::to_vec_named(&compact)
+    }
+
+    /// Read a tree written by [`Self::to_msgpack`]
+    #[cfg(feature = "msgpack")]
+    pub fn from_msgpack(bytes: &[u8]) -> Result<OscNode, rmp_serde::decode::Error> {
+        let mut value: serde_json::Value = rmp_serde::from_slice(bytes)?;
+        fill_full_paths(&mut value, "/");
+        serde_json::from_value(value).map_err(serde::de::Error::custom)
+    }
+
+    /// The tree as printed by `Display`, handy in logs and tests
+    pub fn display_tree(root: &OscNode) -> String {
+        root.to_string()
+    }
+
+    /// Annotate the node with an application-specific `EXTENDED_TYPE`
+    pub fn with_extended_type(mut self, extended_type: impl Into<String>) -> Self {
+        self.extended_type = Some(extended_type.into());
+        self
+    }
+
+    /// Register the address of an observed OSC message as a method
+    ///
+    /// The typetag is derived from the message arguments. Access is
+    /// `Read` since the remote was seen sending it. Goes through
+    /// [`Self::try_add_method`], so pattern addresses like `/a/*` and
+    /// messages without arguments are refused.
+    #[cfg(feature = "rosc")]
+    pub fn register_from_osc_message(
+        root: &mut OscNode,
+        msg: &rosc::OscMessage,
+    ) -> Result<(), OscNodeError> {
+        let mut typetag = String::new();
+        for arg in &msg.args {
+            push_osc_typetag(&mut typetag, arg);
+        }
+        Self::try_add_method(root, &msg.addr, Access::Read, &typetag)
+    }
 
+    /// Like [`Self::register_from_osc_message`], walking bundles recursively
+    ///
+    /// Stops at the first message that can't be registered.
+    #[cfg(feature = "rosc")]
+    pub fn register_from_osc_packet(
+        root: &mut OscNode,
+        packet: &rosc::OscPacket,
+    ) -> Result<(), OscNodeError> {
+        match packet {
+            rosc::OscPacket::Message(msg) => Self::register_from_osc_message(root, msg),
+            rosc::OscPacket::Bundle(bundle) => {
+                for packet in &bundle.content {
+                    Self::register_from_osc_packet(root, packet)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Depth-first, pre-order traversal: every node comes before its children
+    ///
+    /// Includes `self`. Siblings come out in the reverse of `contents`
+    /// order, which is a `HashMap`'s and so arbitrary: sort by `full_path`
+    /// if order matters.
+    pub fn iter(&self) -> OscNodeIter<'_> {
+        OscNodeIter { stack: vec![self] }
+    }
+
+    /// Same traversal as [`Self::iter`], with mutable access to each node
+    pub fn iter_mut(&mut self) -> OscNodeIterMut<'_> {
+        OscNodeIterMut { stack: vec![self] }
+    }
+
+    /// Names of the direct children, in the same order as [`Self::children`]
+    pub fn child_names(&self) -> impl Iterator<Item = &str> {
+        self.contents.keys().map(|name| name.as_str())
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = &OscNode> {
+        self.contents.values()
+    }
+
+    pub fn ensure_path<'a>(root: &'a mut OscNode, path: &str) -> &'a mut OscNode {
+        Self::ensure_components(root, &lenient_components(path))
+    }
+
+    /// [`Self::ensure_path`] for paths that aren't known to be valid
+    ///
+    /// Fails without touching the tree unless `path` passes
+    /// [`path_components`] and [`validate_osc_path`]. A trailing `/` is fine.
+    pub fn try_ensure_path<'a>(root: &'a mut OscNode, path: &str) -> Result<&'a mut OscNode, OscNodeError> {
+        let invalid = || OscNodeError::InvalidOscPath(path.to_string());
+        let components = path_components(path).map_err(|_| invalid())?;
+        validate_osc_path(&format!("/{}", components.join("/"))).map_err(|_| invalid())?;
+        Ok(Self::ensure_components(root, &components))
+    }
+
+    /// Walks down `components`, creating containers for anything missing
+    fn ensure_components<'a>(root: &'a mut OscNode, components: &[&str]) -> &'a mut OscNode {
+        let mut current = root;
         let mut base = String::new();
-        for part in parts.by_ref() {
+        for part in components {
             base.push('/');
             base.push_str(part);
-            let key = part.to_string();
             current = current
                 .contents
-                .entry(key.clone())
+                .entry(part.to_string())
                 .or_insert_with(|| OscNode::new_container(&base));
         }
         current
     }
 
     pub fn add_method(root: &mut OscNode, path: &str, access: Access, typetag: &str) {
-        let parent_path = match path.rfind('/') {
-            Some(idx) if idx > 0 => &path[..idx],
-            _ => "/",
+        Self::insert_method(root, OscNode::new_method(path, access, typetag));
+    }
+
+    /// [`Self::add_method`] for paths and typetags that aren't known to be
+    /// valid, see [`Self::try_new_method`]
+    pub fn try_add_method(
+        root: &mut OscNode,
+        path: &str,
+        access: Access,
+        typetag: &str,
+    ) -> Result<(), OscNodeError> {
+        Self::insert_method(root, OscNode::try_new_method(path, access, typetag)?);
+        Ok(())
+    }
+
+    /// Puts `method` at its `FULL_PATH`, replacing whatever was there
+    pub(crate) fn insert_method(root: &mut OscNode, method: OscNode) {
+        let components = lenient_components(&method.full_path);
+        let Some((name, parents)) = components.split_last() else {
+            return;
         };
-        let name = path_name(path).unwrap_or_else(|| path.trim_matches('/').to_string());
+        let name = name.to_string();
+        Self::ensure_components(root, parents).contents.insert(name, method);
+    }
 
-        let parent = Self::ensure_path(root, parent_path);
-        parent.contents.insert(
-            name.clone(),
-            OscNode::new_method(path, access, typetag),
-        );
+    /// Every node matching `pattern`, sorted by `FULL_PATH`
+    ///
+    /// A `*` component matches any single component and `**` matches any
+    /// number of them, including none: `/avatar/parameters/*` is every
+    /// parameter, `/**/input` is every `input` node anywhere.
+    pub fn glob<'a>(root: &'a OscNode, pattern: &str) -> Vec<&'a OscNode> {
+        let parts: Vec<&str> = pattern.split('/').filter(|part| !part.is_empty()).collect();
+        let mut matches = Vec::new();
+        glob_into(root, &parts, &mut matches);
+
+        matches.sort_by(|a, b| a.full_path.cmp(&b.full_path));
+        matches.dedup_by(|a, b| a.full_path == b.full_path);
+        matches
+    }
+
+    /// Like [`Self::add_method`], with an initial `VALUE` served to clients
+    ///
+    /// Fails without touching the tree if `value` doesn't fit `typetag`,
+    /// see [`check_value_matches_typetag`].
+    pub fn add_method_with_value(
+        root: &mut OscNode,
+        path: &str,
+        access: Access,
+        typetag: &str,
+        value: serde_json::Value,
+    ) -> Result<(), OscNodeError> {
+        let mut method = OscNode::try_new_method(path, access, typetag)?;
+        check_value_matches_typetag(typetag, &value).map_err(|source| OscNodeError::InvalidValue {
+            path: path.to_string(),
+            source,
+        })?;
+
+        method.value = Some(value);
+        Self::insert_method(root, method);
+        Ok(())
+    }
+
+    /// Freeze the `VALUE` of the node at `path`, e.g. while an animation plays
+    ///
+    /// `RunningServer::set_value` refuses to touch locked nodes. Returns
+    /// false if there is no node at `path`.
+    pub fn lock(root: &mut OscNode, path: &str) -> bool {
+        Self::set_locked(root, path, true)
+    }
+
+    /// Undo [`Self::lock`]
+    pub fn unlock(root: &mut OscNode, path: &str) -> bool {
+        Self::set_locked(root, path, false)
+    }
+
+    pub fn is_locked(root: &OscNode, path: &str) -> bool {
+        Self::get_node(root, path).is_some_and(|node| node.locked)
+    }
+
+    fn set_locked(root: &mut OscNode, path: &str, locked: bool) -> bool {
+        match Self::get_node_mut(root, path) {
+            Some(node) => {
+                node.locked = locked;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Change the access of the node at `path`, leaving everything else as is
+    ///
+    /// Returns `false` if there is no such node.
+    pub fn set_access(root: &mut OscNode, path: &str, access: Access) -> bool {
+        match Self::get_node_mut(root, path) {
+            Some(node) => {
+                node.access = Some(access);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Node at `path`, or `None` if any part of it doesn't exist
+    pub fn get_node<'a>(root: &'a OscNode, path: &str) -> Option<&'a OscNode> {
+        let mut current = root;
+        for part in path_components(path).ok()? {
+            current = current.contents.get(part)?;
+        }
+        Some(current)
+    }
+
+    pub fn get_node_mut<'a>(root: &'a mut OscNode, path: &str) -> Option<&'a mut OscNode> {
+        let mut current = root;
+        for part in path_components(path).ok()? {
+            current = current.contents.get_mut(part)?;
+        }
+        Some(current)
+    }
+
+    /// Detach the node at `path` along with everything below it
+    ///
+    /// Returns `None` if there is no such node. The root can't be removed.
+    pub fn remove_node(root: &mut OscNode, path: &str) -> Option<OscNode> {
+        let name = path_name(path.trim_end_matches('/'))?;
+        Self::get_node_mut(root, parent_path(path.trim_end_matches('/')))?
+            .contents
+            .remove(&name)
+    }
+
+    /// Move the subtree at `old_path` to `new_path`, fixing up every `FULL_PATH`
+    ///
+    /// Missing parents of `new_path` are created as containers.
+    pub fn rename_path(root: &mut OscNode, old_path: &str, new_path: &str) -> Result<(), OscNodeError> {
+        let old_path = old_path.trim_end_matches('/');
+        let new_path = new_path.trim_end_matches('/');
+
+        if old_path.is_empty() {
+            return Err(OscNodeError::InvalidPath("/".to_string()));
+        }
+        if new_path.is_empty() || new_path.starts_with(&format!("{}/", old_path)) {
+            let shown = if new_path.is_empty() { "/" } else { new_path };
+            return Err(OscNodeError::InvalidPath(shown.to_string()));
+        }
+        if Self::get_node(root, old_path).is_none() {
+            return Err(OscNodeError::PathNotFound(old_path.to_string()));
+        }
+        if Self::get_node(root, new_path).is_some() {
+            return Err(OscNodeError::PathAlreadyExists(new_path.to_string()));
+        }
+
+        let old_name = path_name(old_path).unwrap_or_default();
+        let mut node = Self::get_node_mut(root, parent_path(old_path))
+            .and_then(|parent| parent.contents.remove(&old_name))
+            .ok_or_else(|| OscNodeError::PathNotFound(old_path.to_string()))?;
+
+        node.full_path = new_path.to_string();
+        recompute_children(&mut node);
+
+        let new_name = path_name(new_path).unwrap_or_default();
+        Self::ensure_path(root, parent_path(new_path))
+            .contents
+            .insert(new_name, node);
+        Ok(())
+    }
+
+    /// Re-root the tree at `prefix`, so `/avatar/parameters/X` becomes
+    /// `/parameters/X` for a prefix of `/avatar`
+    pub fn strip_prefix(root: OscNode, prefix: &str) -> Result<OscNode, OscNodeError> {
+        let mut current = root;
+        for part in lenient_components(prefix) {
+            current = current
+                .contents
+                .remove(part)
+                .ok_or_else(|| OscNodeError::PathNotFound(prefix.to_string()))?;
+        }
+        Self::recompute_full_paths(&mut current);
+        Ok(current)
+    }
+
+    /// Move the whole tree below `prefix`, the inverse of [`Self::strip_prefix`]
+    ///
+    /// Useful before merging trees from several sources.
+    pub fn add_prefix(mut root: OscNode, prefix: &str) -> OscNode {
+        if prefix.trim_matches('/').is_empty() {
+            Self::recompute_full_paths(&mut root);
+            return root;
+        }
+
+        let mut new_root = OscNode::new_container("/");
+        *Self::ensure_path(&mut new_root, prefix) = root;
+        Self::recompute_full_paths(&mut new_root);
+        new_root
+    }
+
+    /// Add an alternative type signature to the node at `path`
+    ///
+    /// Missing nodes along the way are created as containers, like
+    /// [`Self::ensure_path`].
+    pub fn add_overload(root: &mut OscNode, path: &str, typetag: &str, description: Option<&str>) {
+        let node = Self::ensure_path(root, path);
+        node.overloads.get_or_insert_with(Vec::new).push(OscOverload {
+            typetag: typetag.to_string(),
+            description: description.map(|d| d.to_string()),
+        });
+    }
+
+    /// Rewrites every `FULL_PATH` in the tree to match where the node actually sits
+    ///
+    /// `root` is treated as `/`. Call this after moving nodes around in
+    /// `contents` by hand.
+    pub fn recompute_full_paths(root: &mut OscNode) {
+        root.full_path = "/".to_string();
+        recompute_children(root);
+    }
+
+    /// Nesting depth of `path` below `root` (`/` is 0, `/a` is 1, `/a/b` is 2)
+    pub fn path_depth(root: &OscNode, path: &str) -> usize {
+        lenient_components(path)
+            .len()
+            .saturating_sub(lenient_components(&root.full_path).len())
+    }
+
+    /// Total number of nodes in the tree, containers and `root` included
+    pub fn node_count(root: &OscNode) -> usize {
+        1 + root.contents.values().map(Self::node_count).sum::<usize>()
+    }
+
+    /// Depth of the deepest node in the tree, with `root` at 0
+    pub fn max_depth(root: &OscNode) -> usize {
+        root.contents
+            .values()
+            .map(|child| 1 + Self::max_depth(child))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Rough size in bytes of the compact JSON for `root`, without serializing it
+    ///
+    /// Ignores string escaping, so it runs a little low for names with
+    /// quotes or control characters. Good enough to decide whether a
+    /// response is worth compressing.
+    pub fn estimated_json_size(root: &OscNode) -> usize {
+        // {"FULL_PATH":""}
+        let mut size = 16 + root.full_path.len();
+        if root.access.is_some() {
+            size += r#","ACCESS":3"#.len();
+        }
+        if let Some(typetag) = &root.typetag {
+            size += r#","TYPE":"""#.len() + typetag.len();
+        }
+        if let Some(value) = &root.value {
+            size += r#","VALUE":"#.len() + estimated_value_size(value);
+        }
+        if !root.contents.is_empty() {
+            size += r#","CONTENTS":{}"#.len() + root.contents.len() - 1;
+            for (name, child) in &root.contents {
+                // "name":
+                size += name.len() + 3 + Self::estimated_json_size(child);
+            }
+        }
+        if let Some(overloads) = &root.overloads {
+            size += r#","OVERLOADS":[]"#.len() + overloads.len().saturating_sub(1);
+            for overload in overloads {
+                size += r#"{"TYPE":""}"#.len() + overload.typetag.len();
+                if let Some(description) = &overload.description {
+                    size += r#","DESCRIPTION":"""#.len() + description.len();
+                }
+            }
+        }
+        if let Some(extended_type) = &root.extended_type {
+            size += r#","EXTENDED_TYPE":"""#.len() + extended_type.len();
+        }
+        if let Some(inherits) = &root.inherits {
+            size += r#","INHERITS":"""#.len() + inherits.len();
+        }
+        size
+    }
+
+    /// JSON Schema (draft-07) describing the value of every method in the tree
+    ///
+    /// Each method becomes a property keyed by its `FULL_PATH`. Single-type
+    /// methods map straight to a JSON type, multi-type methods become a
+    /// fixed-length tuple.
+    pub fn to_json_schema(root: &OscNode) -> serde_json::Value {
+        let mut properties = serde_json::Map::new();
+        for node in Self::find(root, |node| node.typetag.is_some()) {
+            let typetag = node.typetag.as_deref().unwrap_or_default();
+            let schema = if typetag.chars().count() == 1 {
+                typetag_schema(typetag.chars().next().unwrap())
+            } else {
+                let items: Vec<_> = typetag.chars().map(typetag_schema).collect();
+                serde_json::json!({
+                    "type": "array",
+                    "items": items,
+                    "minItems": items.len(),
+                    "maxItems": items.len(),
+                })
+            };
+            properties.insert(node.full_path.clone(), schema);
+        }
+
+        serde_json::json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "title": root.full_path,
+            "type": "object",
+            "properties": properties,
+        })
+    }
+
+    /// Copy of the tree with every `INHERITS` reference filled in
+    ///
+    /// Fields a node leaves unset (`TYPE`, `ACCESS`, `VALUE`, `OVERLOADS`,
+    /// `EXTENDED_TYPE`) are taken from the node it inherits from, following
+    /// chains of inheritance. `INHERITS` is cleared on the result.
+    pub fn resolve_inheritance(root: &OscNode) -> Result<OscNode, OscNodeError> {
+        let mut resolved = root.clone();
+        for node in root.iter().filter(|node| node.inherits.is_some()) {
+            let mut chain = vec![node.full_path.as_str()];
+            let mut current = node;
+            while let Some(target) = &current.inherits {
+                current = Self::get_node(root, target)
+                    .ok_or_else(|| OscNodeError::PathNotFound(target.clone()))?;
+                if chain.contains(&current.full_path.as_str()) {
+                    return Err(OscNodeError::CircularInheritance(node.full_path.clone()));
+                }
+                chain.push(&current.full_path);
+
+                let Some(out) = Self::get_node_mut(&mut resolved, &node.full_path) else {
+                    continue;
+                };
+                out.typetag = out.typetag.take().or_else(|| current.typetag.clone());
+                out.access = out.access.or(current.access);
+                out.value = out.value.take().or_else(|| current.value.clone());
+                out.overloads = out.overloads.take().or_else(|| current.overloads.clone());
+                out.extended_type = out.extended_type.take().or_else(|| current.extended_type.clone());
+            }
+        }
+
+        for node in resolved.iter_mut() {
+            *node.inherits = None;
+        }
+        Ok(resolved)
+    }
+
+    /// Every node below `root` keyed by `FULL_PATH`, for scripting and FFI
+    ///
+    /// Containers are included so their access survives a round trip
+    /// through [`Self::from_flat_map`]. `root` itself is left out.
+    pub fn to_flat_map(root: &OscNode) -> HashMap<String, FlatNodeEntry> {
+        root.iter()
+            .skip(1)
+            .map(|node| {
+                let entry = FlatNodeEntry {
+                    typetag: node.typetag.clone(),
+                    access: node.access,
+                    value: node.value.clone(),
+                };
+                (node.full_path.clone(), entry)
+            })
+            .collect()
+    }
+
+    /// Rebuild a tree from [`Self::to_flat_map`] output
+    ///
+    /// Parents missing from the map are created as containers.
+    pub fn from_flat_map(entries: HashMap<String, FlatNodeEntry>) -> OscNode {
+        let mut root = OscNode::new_container("/");
+        for (path, entry) in entries {
+            let node = Self::ensure_path(&mut root, &path);
+            node.typetag = entry.typetag;
+            node.access = entry.access;
+            node.value = entry.value;
+        }
+        root
+    }
+
+    /// Remove every node below `root` for which `keep` returns false
+    ///
+    /// A removed node takes its whole subtree with it.
+    pub fn retain<F>(root: &mut OscNode, keep: F)
+    where
+        F: Fn(&OscNode) -> bool,
+    {
+        retain_children(root, &keep);
+    }
+
+    /// What [`Self::retain`] would leave of `root`, or `None` if `keep`
+    /// accepts every node
+    ///
+    /// Only the kept nodes are copied, so this is cheaper than cloning the
+    /// tree and then calling [`Self::retain`] on it.
+    pub fn retained<F>(root: &OscNode, keep: F) -> Option<OscNode>
+    where
+        F: Fn(&OscNode) -> bool,
+    {
+        if root.iter().skip(1).all(&keep) {
+            return None;
+        }
+        Some(retained_copy(root, &keep))
+    }
+
+    /// Depth-first search for all nodes matching `predicate`, root included
+    pub fn find<F>(root: &OscNode, predicate: F) -> Vec<&OscNode>
+    where
+        F: Fn(&OscNode) -> bool,
+    {
+        root.iter().filter(|node| predicate(node)).collect()
+    }
+
+    /// `FULL_PATH` of every method in the tree, in [`Self::iter`] order
+    pub fn to_osc_address_list(root: &OscNode) -> Vec<String> {
+        Self::find(root, |node| node.typetag.is_some())
+            .into_iter()
+            .map(|node| node.full_path.clone())
+            .collect()
+    }
+
+    /// Like [`Self::to_osc_address_list`], limited to methods with exactly `access`
+    pub fn to_osc_address_list_filtered(root: &OscNode, access: Access) -> Vec<String> {
+        Self::find(root, |node| node.typetag.is_some() && node.access == Some(access))
+            .into_iter()
+            .map(|node| node.full_path.clone())
+            .collect()
+    }
+
+    pub fn find_by_typetag<'a>(root: &'a OscNode, typetag: &str) -> Vec<&'a OscNode> {
+        Self::find(root, |node| node.typetag.as_deref() == Some(typetag))
+    }
+
+    pub fn find_writable(root: &OscNode) -> Vec<&OscNode> {
+        Self::find(root, |node| {
+            matches!(node.access, Some(Access::Write | Access::ReadWrite))
+        })
+    }
+}
+
+/// Limits for [`OscNode::from_json_str_with_options`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeserializeOptions {
+    pub max_depth: usize,
+    pub max_nodes: usize,
+}
+
+impl Default for DeserializeOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: 32,
+            max_nodes: 100_000,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum OscNodeDeserializeError {
+    /// Carries the configured `max_depth`
+    #[error("Node tree is deeper than {0} levels")]
+    TooDeep(usize),
+
+    /// Carries the configured `max_nodes`
+    #[error("Node tree has more than {0} nodes")]
+    TooManyNodes(usize),
+
+    #[error("Invalid node JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+struct Limits {
+    opts: DeserializeOptions,
+    count: Cell<usize>,
+    /// serde errors are strings, so the real reason is kept here
+    exceeded: Cell<Option<OscNodeDeserializeError>>,
+}
+
+impl Limits {
+    fn exceed<E: de::Error>(&self, err: OscNodeDeserializeError) -> E {
+        let message = err.to_string();
+        self.exceeded.set(Some(err));
+        E::custom(message)
+    }
+}
+
+/// Reads one node as a JSON value, recursing into `CONTENTS` with a limit check
+struct NodeSeed<'a> {
+    limits: &'a Limits,
+    depth: usize,
+}
+
+impl<'de> DeserializeSeed<'de> for NodeSeed<'_> {
+    type Value = serde_json::Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for NodeSeed<'_> {
+    type Value = serde_json::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an OSCQuery node")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let limits = self.limits;
+        if self.depth > limits.opts.max_depth {
+            return Err(limits.exceed(OscNodeDeserializeError::TooDeep(limits.opts.max_depth)));
+        }
+        limits.count.set(limits.count.get() + 1);
+        if limits.count.get() > limits.opts.max_nodes {
+            return Err(limits.exceed(OscNodeDeserializeError::TooManyNodes(limits.opts.max_nodes)));
+        }
+
+        let mut node = serde_json::Map::new();
+        while let Some(key) = map.next_key::<String>()? {
+            let value = if key == "CONTENTS" {
+                map.next_value_seed(ContentsSeed {
+                    limits,
+                    depth: self.depth + 1,
+                })?
+            } else {
+                map.next_value()?
+            };
+            node.insert(key, value);
+        }
+        Ok(serde_json::Value::Object(node))
+    }
+}
+
+struct ContentsSeed<'a> {
+    limits: &'a Limits,
+    depth: usize,
+}
+
+impl<'de> DeserializeSeed<'de> for ContentsSeed<'_> {
+    type Value = serde_json::Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ContentsSeed<'_> {
+    type Value = serde_json::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a map of OSCQuery nodes")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut contents = serde_json::Map::new();
+        while let Some(key) = map.next_key::<String>()? {
+            let child = map.next_value_seed(NodeSeed {
+                limits: self.limits,
+                depth: self.depth,
+            })?;
+            contents.insert(key, child);
+        }
+        Ok(serde_json::Value::Object(contents))
+    }
+}
+
+/// One node in [`OscNode::to_flat_map`], without its children
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FlatNodeEntry {
+    pub typetag: Option<String>,
+    pub access: Option<Access>,
+    pub value: Option<serde_json::Value>,
+}
+
+/// Paths that differ between two trees, each sorted
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NodeDelta {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Present in both trees but with a different typetag or access
+    pub changed: Vec<String>,
+}
+
+impl NodeDelta {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compares the leaf nodes of two trees by `FULL_PATH`
+///
+/// Useful for working out which parameters appeared or disappeared after
+/// a VRChat avatar change.
+pub struct OscNodeDiff;
+
+impl OscNodeDiff {
+    pub fn diff(old: &OscNode, new: &OscNode) -> NodeDelta {
+        let old_leaves = leaves_by_path(old);
+        let new_leaves = leaves_by_path(new);
+
+        let mut delta = NodeDelta::default();
+        for (path, new_node) in &new_leaves {
+            match old_leaves.get(path) {
+                None => delta.added.push(path.to_string()),
+                Some(old_node) => {
+                    if old_node.typetag != new_node.typetag || old_node.access != new_node.access {
+                        delta.changed.push(path.to_string());
+                    }
+                }
+            }
+        }
+        for path in old_leaves.keys() {
+            if !new_leaves.contains_key(path) {
+                delta.removed.push(path.to_string());
+            }
+        }
+
+        delta.added.sort();
+        delta.removed.sort();
+        delta.changed.sort();
+        delta
+    }
+}
+
+fn estimated_value_size(value: &serde_json::Value) -> usize {
+    match value {
+        serde_json::Value::Null => 4,
+        serde_json::Value::Bool(true) => 4,
+        serde_json::Value::Bool(false) => 5,
+        serde_json::Value::Number(n) => n.to_string().len(),
+        serde_json::Value::String(s) => s.len() + 2,
+        serde_json::Value::Array(values) => {
+            2 + values.len().saturating_sub(1) + values.iter().map(estimated_value_size).sum::<usize>()
+        }
+        serde_json::Value::Object(map) => {
+            2 + map.len().saturating_sub(1)
+                + map
+                    .iter()
+                    .map(|(key, value)| key.len() + 3 + estimated_value_size(value))
+                    .sum::<usize>()
+        }
+    }
+}
+
+fn glob_into<'a>(node: &'a OscNode, parts: &[&str], matches: &mut Vec<&'a OscNode>) {
+    let Some((&part, rest)) = parts.split_first() else {
+        matches.push(node);
+        return;
+    };
+
+    match part {
+        "**" => {
+            glob_into(node, rest, matches);
+            for child in node.contents.values() {
+                glob_into(child, parts, matches);
+            }
+        }
+        "*" => {
+            for child in node.contents.values() {
+                glob_into(child, rest, matches);
+            }
+        }
+        name => {
+            if let Some(child) = node.contents.get(name) {
+                glob_into(child, rest, matches);
+            }
+        }
+    }
+}
+
+fn leaves_by_path(root: &OscNode) -> HashMap<&str, &OscNode> {
+    OscNode::find(root, |node| node.contents.is_empty())
+        .into_iter()
+        .map(|node| (node.full_path.as_str(), node))
+        .collect()
+}
+
+fn retain_children<F>(node: &mut OscNode, keep: &F)
+where
+    F: Fn(&OscNode) -> bool,
+{
+    node.contents.retain(|_, child| keep(child));
+    for child in node.contents.values_mut() {
+        retain_children(child, keep);
+    }
+}
+
+/// Puts back the `FULL_PATH`s [`OscNode::to_msgpack`] leaves out
+#[cfg(feature = "msgpack")]
+fn fill_full_paths(node: &mut serde_json::Value, full_path: &str) {
+    let Some(object) = node.as_object_mut() else {
+        return;
+    };
+    let full_path = object
+        .entry("FULL_PATH")
+        .or_insert_with(|| full_path.into())
+        .as_str()
+        .unwrap_or(full_path)
+        .to_string();
+    if let Some(serde_json::Value::Object(contents)) = object.get_mut("CONTENTS") {
+        let base = full_path.trim_end_matches('/');
+        for (name, child) in contents.iter_mut() {
+            fill_full_paths(child, &format!("{}/{}", base, name));
+        }
+    }
+}
+
+fn retained_copy<F>(node: &OscNode, keep: &F) -> OscNode
+where
+    F: Fn(&OscNode) -> bool,
+{
+    OscNode {
+        full_path: node.full_path.clone(),
+        access: node.access,
+        typetag: node.typetag.clone(),
+        value: node.value.clone(),
+        contents: node
+            .contents
+            .iter()
+            .filter(|(_, child)| keep(child))
+            .map(|(name, child)| (name.clone(), retained_copy(child, keep)))
+            .collect(),
+        overloads: node.overloads.clone(),
+        extended_type: node.extended_type.clone(),
+        inherits: node.inherits.clone(),
+        locked: node.locked,
+    }
+}
+
+/// Rewrites the `FULL_PATH` of everything below `node` from its own
+pub(crate) fn recompute_children(node: &mut OscNode) {
+    let base = node.full_path.trim_end_matches('/').to_string();
+    for (name, child) in node.contents.iter_mut() {
+        child.full_path = format!("{}/{}", base, name);
+        recompute_children(child);
+    }
+}
+
+/// Depth-first, pre-order iterator over a tree, see [`OscNode::iter`]
+pub struct OscNodeIter<'a> {
+    stack: Vec<&'a OscNode>,
+}
+
+impl<'a> Iterator for OscNodeIter<'a> {
+    type Item = &'a OscNode;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.stack.extend(node.contents.values());
+        Some(node)
+    }
+}
+
+/// Mutable view of a single node, yielded by [`OscNodeIterMut`]
+///
+/// `contents` is left out since the iterator is still walking it.
+pub struct OscNodeMut<'a> {
+    pub full_path: &'a mut String,
+    pub access: &'a mut Option<Access>,
+    pub typetag: &'a mut Option<String>,
+    pub value: &'a mut Option<serde_json::Value>,
+    pub overloads: &'a mut Option<Vec<OscOverload>>,
+    pub extended_type: &'a mut Option<String>,
+    pub inherits: &'a mut Option<String>,
+    pub locked: &'a mut bool,
+}
+
+/// Mutable counterpart of [`OscNodeIter`], see [`OscNode::iter_mut`]
+pub struct OscNodeIterMut<'a> {
+    stack: Vec<&'a mut OscNode>,
+}
+
+impl<'a> Iterator for OscNodeIterMut<'a> {
+    type Item = OscNodeMut<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let OscNode {
+            full_path,
+            access,
+            typetag,
+            value,
+            contents,
+            overloads,
+            extended_type,
+            inherits,
+            locked,
+        } = self.stack.pop()?;
+        self.stack.extend(contents.values_mut());
+        Some(OscNodeMut {
+            full_path,
+            access,
+            typetag,
+            value,
+            overloads,
+            extended_type,
+            inherits,
+            locked,
+        })
+    }
+}
+
+#[cfg(feature = "rosc")]
+fn push_osc_typetag(typetag: &mut String, arg: &rosc::OscType) {
+    use rosc::OscType;
+
+    match arg {
+        OscType::Int(_) => typetag.push('i'),
+        OscType::Float(_) => typetag.push('f'),
+        OscType::String(_) => typetag.push('s'),
+        OscType::Blob(_) => typetag.push('b'),
+        OscType::Time(_) => typetag.push('t'),
+        OscType::Long(_) => typetag.push('h'),
+        OscType::Double(_) => typetag.push('d'),
+        OscType::Char(_) => typetag.push('c'),
+        OscType::Color(_) => typetag.push('r'),
+        OscType::Midi(_) => typetag.push('m'),
+        OscType::Bool(true) => typetag.push('T'),
+        OscType::Bool(false) => typetag.push('F'),
+        OscType::Nil => typetag.push('N'),
+        OscType::Inf => typetag.push('I'),
+        OscType::Array(array) => {
+            typetag.push('[');
+            for arg in &array.content {
+                push_osc_typetag(typetag, arg);
+            }
+            typetag.push(']');
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum OscNodeError {
+    #[error("No node at {0}")]
+    PathNotFound(String),
+
+    #[error("A node already exists at {0}")]
+    PathAlreadyExists(String),
+
+    /// Moving the root, onto the root, or into its own subtree
+    #[error("Cannot move a node to or from {0}")]
+    InvalidPath(String),
+
+    #[error("{0} is locked")]
+    Locked(String),
+
+    #[error("{0:?} is not a valid method path")]
+    InvalidMethodPath(String),
+
+    #[error("{0:?} is not a valid typetag")]
+    InvalidTypetag(String),
+
+    #[error("{0:?} is not a valid OSC address")]
+    InvalidOscPath(String),
+
+    /// An edit to a running server would break `with_max_depth`
+    #[error("Edit would make the tree {depth} levels deep, more than the configured maximum of {max}")]
+    MaxDepthExceeded { depth: usize, max: usize },
+
+    /// An edit to a running server would break `with_max_nodes`
+    #[error("Edit would leave {count} methods, more than the configured maximum of {max}")]
+    NodeLimitExceeded { count: usize, max: usize },
+
+    #[error("INHERITS of {0} loops back on itself")]
+    CircularInheritance(String),
+
+    #[error("Invalid VALUE for {path}: {source}")]
+    InvalidValue {
+        path: String,
+        #[source]
+        source: OscValueTypeError,
+    },
+}
+
+/// Why [`path_components`] rejected a path
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum OscPathError {
+    #[error("{0:?} doesn't start with '/'")]
+    MissingLeadingSlash(String),
+
+    #[error("{0:?} has an empty component")]
+    EmptyComponent(String),
+}
+
+/// Why a `VALUE` doesn't fit a typetag, see [`check_value_matches_typetag`]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum OscValueTypeError {
+    #[error("TYPE has {expected} arguments but VALUE has {actual}")]
+    LengthMismatch { expected: usize, actual: usize },
+
+    #[error("argument {index} should be {tag:?} but is {value}")]
+    WrongType {
+        index: usize,
+        tag: char,
+        value: serde_json::Value,
+    },
+
+    #[error("{0:?} is not a valid OSC type tag")]
+    InvalidTypetag(char),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum OscNodeValidationError {
+    #[error("{path}: TYPE has {expected} arguments but VALUE has {actual}")]
+    ValueLengthMismatch {
+        path: String,
+        expected: usize,
+        actual: usize,
+    },
+
+    #[error("{path}: {tag:?} is not a valid OSC type tag")]
+    InvalidTypetag { path: String, tag: char },
+
+    #[error("{path}: node has both CONTENTS and a TYPE")]
+    ContainerWithTypetag { path: String },
+}
+
+/// Checks `node` and everything below it, collecting every violation
+pub fn validate_node(node: &OscNode) -> Result<(), Vec<OscNodeValidationError>> {
+    let mut errors = Vec::new();
+    for node in OscNode::find(node, |_| true) {
+        validate_single(node, &mut errors);
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// `FULL_PATH` of every container whose `ACCESS` isn't `None`
+///
+/// The spec requires nodes with `CONTENTS` to have `ACCESS` 0.
+pub fn validate_access_consistency(root: &OscNode) -> Vec<String> {
+    validate_access_consistency_allowing(root, &[])
+}
+
+/// [`validate_access_consistency`] that also accepts the containers whose
+/// `FULL_PATH` is in `allowed`, for trees that opt out of the spec
+pub fn validate_access_consistency_allowing(root: &OscNode, allowed: &[String]) -> Vec<String> {
+    root.iter()
+        .filter(|node| !node.contents.is_empty())
+        .filter(|node| node.access != Some(Access::None))
+        .filter(|node| !allowed.contains(&node.full_path))
+        .map(|node| node.full_path.clone())
+        .collect()
+}
+
+fn validate_single(node: &OscNode, errors: &mut Vec<OscNodeValidationError>) {
+    let Some(typetag) = &node.typetag else {
+        return;
+    };
+
+    if !node.contents.is_empty() {
+        errors.push(OscNodeValidationError::ContainerWithTypetag {
+            path: node.full_path.clone(),
+        });
+    }
+
+    let invalid: Vec<char> = typetag.chars().filter(|&c| !is_osc_type(c)).collect();
+    for tag in &invalid {
+        errors.push(OscNodeValidationError::InvalidTypetag {
+            path: node.full_path.clone(),
+            tag: *tag,
+        });
+    }
+
+    if let (Some(value), true) = (&node.value, invalid.is_empty()) {
+        let expected = typetag_arity(typetag);
+        let actual = match value {
+            serde_json::Value::Array(values) => values.len(),
+            _ => 1,
+        };
+        if expected != actual {
+            errors.push(OscNodeValidationError::ValueLengthMismatch {
+                path: node.full_path.clone(),
+                expected,
+                actual,
+            });
+        }
+    }
+}
+
+/// Checks that each element of `value` has the JSON type `typetag` calls for
+///
+/// `value` is normally an array with one element per argument; a bare value
+/// is accepted for single-argument typetags. Bracketed OSC arrays must be
+/// JSON arrays. Blobs, timetags, colours and MIDI accept anything since they
+/// have no natural JSON type.
+pub fn check_value_matches_typetag(
+    typetag: &str,
+    value: &serde_json::Value,
+) -> Result<(), OscValueTypeError> {
+    if let Some(tag) = typetag.chars().find(|&c| !is_osc_type(c)) {
+        return Err(OscValueTypeError::InvalidTypetag(tag));
+    }
+
+    match value {
+        serde_json::Value::Array(values) => check_value_args(typetag, values),
+        single => check_value_args(typetag, std::slice::from_ref(single)),
+    }
+}
+
+fn check_value_args(typetag: &str, values: &[serde_json::Value]) -> Result<(), OscValueTypeError> {
+    let args = typetag_args(typetag);
+    if args.len() != values.len() {
+        return Err(OscValueTypeError::LengthMismatch {
+            expected: args.len(),
+            actual: values.len(),
+        });
+    }
+
+    for (index, (arg, value)) in args.into_iter().zip(values).enumerate() {
+        let tag = arg.chars().next().unwrap_or_default();
+        let matches = match (tag, value) {
+            ('[', serde_json::Value::Array(inner)) => {
+                let inner_tag = arg.trim_start_matches('[').trim_end_matches(']');
+                check_value_args(inner_tag, inner).is_ok()
+            }
+            ('[', _) => false,
+            ('f' | 'd', value) => value.is_number(),
+            ('i' | 'h', value) => value.is_i64() || value.is_u64(),
+            ('s' | 'S' | 'c', value) => value.is_string(),
+            ('T' | 'F', value) => value.is_boolean(),
+            ('N' | 'I', value) => value.is_null(),
+            _ => true,
+        };
+        if !matches {
+            return Err(OscValueTypeError::WrongType {
+                index,
+                tag,
+                value: value.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Splits a typetag into top-level arguments, keeping `[...]` groups whole
+fn typetag_args(typetag: &str) -> Vec<&str> {
+    let mut args = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (idx, tag) in typetag.char_indices() {
+        match tag {
+            '[' => {
+                if depth == 0 {
+                    start = idx;
+                }
+                depth += 1;
+            }
+            ']' => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    args.push(&typetag[start..=idx]);
+                }
+            }
+            _ if depth == 0 => args.push(&typetag[idx..idx + tag.len_utf8()]),
+            _ => {}
+        }
+    }
+    args
+}
+
+/// Non-empty, known tags only, with balanced array brackets
+fn is_valid_typetag(typetag: &str) -> bool {
+    let mut depth = 0usize;
+    for tag in typetag.chars() {
+        match tag {
+            '[' => depth += 1,
+            ']' => match depth.checked_sub(1) {
+                Some(outer) => depth = outer,
+                None => return false,
+            },
+            _ if is_osc_type(tag) => {}
+            _ => return false,
+        }
+    }
+    !typetag.is_empty() && depth == 0
+}
+
+fn is_osc_type(tag: char) -> bool {
+    matches!(
+        tag,
+        'i' | 'f' | 's' | 'b' | 'h' | 't' | 'd' | 'S' | 'c' | 'r' | 'm' | 'T' | 'F' | 'N' | 'I'
+            | '[' | ']'
+    )
+}
+
+/// Number of top-level arguments, counting a bracketed OSC array as one
+fn typetag_arity(typetag: &str) -> usize {
+    let mut depth = 0usize;
+    let mut count = 0;
+    for tag in typetag.chars() {
+        match tag {
+            '[' => {
+                if depth == 0 {
+                    count += 1;
+                }
+                depth += 1;
+            }
+            ']' => depth = depth.saturating_sub(1),
+            _ if depth == 0 => count += 1,
+            _ => {}
+        }
+    }
+    count
+}
+
+fn typetag_schema(tag: char) -> serde_json::Value {
+    match tag {
+        'f' | 'd' => serde_json::json!({ "type": "number" }),
+        'i' | 'h' => serde_json::json!({ "type": "integer" }),
+        's' | 'S' | 'c' => serde_json::json!({ "type": "string" }),
+        'T' | 'F' => serde_json::json!({ "type": "boolean" }),
+        'N' | 'I' => serde_json::json!({ "type": "null" }),
+        // Blobs, timetags, colours and MIDI have no natural JSON type
+        _ => serde_json::json!({}),
+    }
+}
+
+/// Every ancestor of `path`, from `/` down to its parent
+///
+/// `/avatar/parameters/GestureLeft` gives `["/", "/avatar", "/avatar/parameters"]`.
+/// The root itself has no ancestors.
+pub fn ancestors(path: &str) -> Vec<&str> {
+    let path = path.trim_end_matches('/');
+    if path.is_empty() {
+        return Vec::new();
+    }
+
+    let mut out = vec!["/"];
+    for (idx, c) in path.char_indices().skip(1) {
+        if c == '/' {
+            out.push(&path[..idx]);
+        }
+    }
+    out
+}
+
+/// Checks that `path` is a usable OSC address
+///
+/// It has to start with `/`, have no empty components and contain none of
+/// the characters OSC reserves for patterns (` #*,?[]{}`) or control
+/// characters. `/` on its own is the root and is fine.
+pub fn validate_osc_path(path: &str) -> Result<(), OscNodeError> {
+    let invalid = || OscNodeError::InvalidOscPath(path.to_string());
+    if path == "/" {
+        return Ok(());
+    }
+    let rest = path.strip_prefix('/').ok_or_else(invalid)?;
+    for part in rest.split('/') {
+        let reserved = |c: char| c.is_control() || " #*,?[]{}".contains(c);
+        if part.is_empty() || part.contains(reserved) {
+            return Err(invalid());
+        }
+    }
+    Ok(())
+}
+
+/// Splits an absolute OSC path into its components
+///
+/// `/avatar/parameters` gives `["avatar", "parameters"]` and `/` gives
+/// nothing. A single trailing `/`, as in `/avatar/`, is allowed.
+pub fn path_components(path: &str) -> Result<Vec<&str>, OscPathError> {
+    let rest = path
+        .strip_prefix('/')
+        .ok_or_else(|| OscPathError::MissingLeadingSlash(path.to_string()))?;
+    if rest.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let components: Vec<&str> = rest.strip_suffix('/').unwrap_or(rest).split('/').collect();
+    if components.iter().any(|part| part.is_empty()) {
+        return Err(OscPathError::EmptyComponent(path.to_string()));
+    }
+    Ok(components)
+}
+
+/// [`path_components`] for the infallible builders, which also take
+/// relative paths and drop empty components instead of failing
+pub(crate) fn lenient_components(path: &str) -> Vec<&str> {
+    path_components(path).unwrap_or_else(|_| path.split('/').filter(|part| !part.is_empty()).collect())
+}
+
+fn parent_path(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(idx) if idx > 0 => &path[..idx],
+        _ => "/",
     }
 }
 
@@ -96,3 +1712,405 @@ fn path_name(path: &str) -> Option<String> {
         .next()
         .map(|s| s.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn try_new_method_rejects_bad_paths() {
+        for path in ["avatar", "/", "//", "/a//b"] {
+            assert_eq!(
+                OscNode::try_new_method(path, Access::Read, "f").unwrap_err(),
+                OscNodeError::InvalidMethodPath(path.to_string()),
+            );
+        }
+        for path in ["/a/", "/a b", "/a/*", "/a/{b,c}", "/a#"] {
+            assert_eq!(
+                OscNode::try_new_method(path, Access::Read, "f").unwrap_err(),
+                OscNodeError::InvalidOscPath(path.to_string()),
+            );
+        }
+        assert!(OscNode::try_new_method("/avatar/parameters/VRCEmote", Access::Read, "i").is_ok());
+    }
+
+    #[test]
+    fn try_ensure_path_leaves_tree_alone_on_error() {
+        let mut root = OscNode::new_container("/");
+        for path in ["a", "/a//b", "/a b/c", "/a/\0", "/a/*/c"] {
+            assert_eq!(
+                OscNode::try_ensure_path(&mut root, path).unwrap_err(),
+                OscNodeError::InvalidOscPath(path.to_string()),
+            );
+        }
+        assert_eq!(OscNode::node_count(&root), 1);
+
+        let node = OscNode::try_ensure_path(&mut root, "/a/b/").unwrap();
+        assert_eq!(node.full_path, "/a/b");
+        assert_eq!(OscNode::node_count(&root), 3);
+    }
+
+    #[test]
+    fn eq_ignores_lock_and_stale_child_paths() {
+        let mut a = OscNode::new_container("/");
+        OscNode::add_method(&mut a, "/avatar/change", Access::Read, "s");
+        let mut b = a.clone();
+
+        OscNode::lock(&mut b, "/avatar/change");
+        OscNode::get_node_mut(&mut b, "/avatar").unwrap().full_path = "/stale".to_string();
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+
+        OscNode::get_node_mut(&mut b, "/avatar/change").unwrap().access = Some(Access::Write);
+        assert_ne!(a, b);
+
+        // The outermost path is still compared
+        let mut c = a.clone();
+        c.full_path = "/other".to_string();
+        assert_ne!(a, c);
+    }
+
+    /// `/`, then `/avatar` with three parameters and `/chatbox` with one method
+    fn sample_tree() -> OscNode {
+        let mut root = OscNode::new_container("/");
+        OscNode::add_method(&mut root, "/avatar/change", Access::Read, "s");
+        OscNode::add_method(&mut root, "/avatar/parameters/GestureLeft", Access::ReadWrite, "i");
+        OscNode::add_method(&mut root, "/avatar/parameters/GestureRight", Access::ReadWrite, "i");
+        OscNode::add_method(&mut root, "/avatar/parameters/Voice", Access::Read, "f");
+        OscNode::add_method(&mut root, "/chatbox/input", Access::Write, "sTT");
+        root
+    }
+
+    #[test]
+    fn retained_matches_retain() {
+        let root = sample_tree();
+        assert!(OscNode::retained(&root, |_| true).is_none());
+
+        let keep = |node: &OscNode| node.access != Some(Access::Read) || node.full_path == "/avatar/change";
+        let mut expected = root.clone();
+        OscNode::retain(&mut expected, keep);
+        let pruned = OscNode::retained(&root, keep).unwrap();
+        assert_eq!(pruned, expected);
+        assert!(OscNode::get_node(&pruned, "/avatar/parameters/Voice").is_none());
+        assert!(OscNode::get_node(&pruned, "/avatar/change").is_some());
+    }
+
+    #[test]
+    fn access_consistency_opt_in_is_per_path() {
+        let mut root = sample_tree();
+        OscNode::get_node_mut(&mut root, "/avatar").unwrap().access = Some(Access::Read);
+        OscNode::get_node_mut(&mut root, "/chatbox").unwrap().access = Some(Access::Read);
+
+        let mut flagged = validate_access_consistency(&root);
+        flagged.sort();
+        assert_eq!(flagged, ["/avatar", "/chatbox"]);
+        let allowed = ["/avatar".to_string()];
+        assert_eq!(validate_access_consistency_allowing(&root, &allowed), ["/chatbox"]);
+    }
+
+    #[test]
+    fn iter_is_pre_order_over_every_node() {
+        let mut root = sample_tree();
+        assert_eq!(root.iter_mut().count(), OscNode::node_count(&root));
+
+        let paths: Vec<&str> = root.iter().map(|node| node.full_path.as_str()).collect();
+        assert_eq!(paths.len(), OscNode::node_count(&root));
+
+        for (idx, path) in paths.iter().enumerate() {
+            for ancestor in ancestors(path) {
+                let parent_idx = paths.iter().position(|p| *p == ancestor).unwrap();
+                assert!(parent_idx < idx, "{} came before {}", path, ancestor);
+            }
+        }
+    }
+
+    fn sorted_paths(nodes: Vec<&OscNode>) -> Vec<&str> {
+        let mut paths: Vec<&str> = nodes.into_iter().map(|node| node.full_path.as_str()).collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn find_by_predicate_typetag_and_access() {
+        let root = sample_tree();
+
+        let containers = OscNode::find(&root, |node| node.typetag.is_none());
+        assert_eq!(sorted_paths(containers), ["/", "/avatar", "/avatar/parameters", "/chatbox"]);
+
+        let ints = OscNode::find_by_typetag(&root, "i");
+        assert_eq!(
+            sorted_paths(ints),
+            ["/avatar/parameters/GestureLeft", "/avatar/parameters/GestureRight"]
+        );
+        assert!(OscNode::find_by_typetag(&root, "d").is_empty());
+
+        let writable = OscNode::find_writable(&root);
+        assert_eq!(
+            sorted_paths(writable),
+            [
+                "/avatar/parameters/GestureLeft",
+                "/avatar/parameters/GestureRight",
+                "/chatbox/input"
+            ]
+        );
+    }
+
+    #[test]
+    fn recompute_full_paths_fixes_moved_subtree() {
+        let mut root = sample_tree();
+
+        // Move by hand, leaving every FULL_PATH in the subtree stale
+        let parameters = root.contents.get_mut("avatar").unwrap().contents.remove("parameters").unwrap();
+        root.contents.get_mut("chatbox").unwrap().contents.insert("params".to_string(), parameters);
+        assert_eq!(
+            OscNode::get_node(&root, "/chatbox/params/Voice").unwrap().full_path,
+            "/avatar/parameters/Voice"
+        );
+
+        OscNode::recompute_full_paths(&mut root);
+        // Every stored path leads back to the node itself
+        for node in root.iter() {
+            assert!(std::ptr::eq(OscNode::get_node(&root, &node.full_path).unwrap(), node));
+        }
+        assert_eq!(OscNode::get_node(&root, "/chatbox/params").unwrap().full_path, "/chatbox/params");
+        assert_eq!(
+            OscNode::get_node(&root, "/chatbox/params/Voice").unwrap().full_path,
+            "/chatbox/params/Voice"
+        );
+    }
+
+    #[test]
+    fn child_names_match_children() {
+        let root = sample_tree();
+        let parameters = OscNode::get_node(&root, "/avatar/parameters").unwrap();
+
+        let names: Vec<&str> = parameters.child_names().collect();
+        let children: Vec<&OscNode> = parameters.children().collect();
+        assert_eq!(names.len(), 3);
+        for (name, child) in names.iter().zip(&children) {
+            assert_eq!(child.full_path, format!("/avatar/parameters/{}", name));
+        }
+
+        let mut sorted = names.clone();
+        sorted.sort();
+        assert_eq!(sorted, ["GestureLeft", "GestureRight", "Voice"]);
+
+        let leaf = OscNode::get_node(&root, "/chatbox/input").unwrap();
+        assert_eq!(leaf.child_names().count(), 0);
+        assert_eq!(leaf.children().count(), 0);
+    }
+
+    #[test]
+    fn node_count_and_depth() {
+        let root = sample_tree();
+        assert_eq!(OscNode::node_count(&root), 9);
+        assert_eq!(OscNode::max_depth(&root), 3);
+
+        let avatar = OscNode::get_node(&root, "/avatar").unwrap();
+        assert_eq!(OscNode::node_count(avatar), 6);
+        assert_eq!(OscNode::max_depth(avatar), 2);
+
+        let empty = OscNode::new_container("/");
+        assert_eq!(OscNode::node_count(&empty), 1);
+        assert_eq!(OscNode::max_depth(&empty), 0);
+    }
+
+    #[test]
+    fn osc_address_lists_skip_containers() {
+        let mut root = sample_tree();
+        OscNode::get_node_mut(&mut root, "/avatar").unwrap().access = Some(Access::Read);
+
+        let mut all = OscNode::to_osc_address_list(&root);
+        all.sort();
+        assert_eq!(
+            all,
+            [
+                "/avatar/change",
+                "/avatar/parameters/GestureLeft",
+                "/avatar/parameters/GestureRight",
+                "/avatar/parameters/Voice",
+                "/chatbox/input",
+            ]
+        );
+
+        // `/avatar` is `Read` too, but only methods are listed
+        let mut read = OscNode::to_osc_address_list_filtered(&root, Access::Read);
+        read.sort();
+        assert_eq!(read, ["/avatar/change", "/avatar/parameters/Voice"]);
+        assert_eq!(OscNode::to_osc_address_list_filtered(&root, Access::Write), ["/chatbox/input"]);
+        assert!(OscNode::to_osc_address_list_filtered(&root, Access::None).is_empty());
+    }
+
+    #[test]
+    fn display_tree_snapshot() {
+        let mut root = sample_tree();
+        OscNode::get_node_mut(&mut root, "/avatar/parameters/Voice").unwrap().value = Some(json!([0.25]));
+
+        let expected = "\
+/
+├── avatar (None)
+│   ├── change s (Read)
+│   └── parameters (None)
+│       ├── GestureLeft i (ReadWrite)
+│       ├── GestureRight i (ReadWrite)
+│       └── Voice f (Read) = [0.25]
+└── chatbox (None)
+    └── input sTT (Write)";
+        assert_eq!(OscNode::display_tree(&root), expected);
+        assert_eq!(root.to_string(), expected);
+    }
+
+    #[test]
+    fn estimated_json_size_is_close() {
+        let mut root = sample_tree();
+        OscNode::add_method_with_value(&mut root, "/avatar/parameters/Scale", Access::Read, "f", json!([1.5]))
+            .unwrap();
+        OscNode::add_overload(&mut root, "/chatbox/input", "s", Some("text only"));
+        for idx in 0..200 {
+            let path = format!("/avatar/parameters/Param{}", idx);
+            OscNode::add_method_with_value(&mut root, &path, Access::ReadWrite, "i", json!([idx])).unwrap();
+        }
+
+        let actual = serde_json::to_vec(&root).unwrap().len();
+        let estimate = OscNode::estimated_json_size(&root);
+        let error = estimate.abs_diff(actual) as f64 / actual as f64;
+        assert!(error < 0.2, "estimated {} bytes, actual {}", estimate, actual);
+    }
+
+    fn glob_paths<'a>(root: &'a OscNode, pattern: &str) -> Vec<&'a str> {
+        OscNode::glob(root, pattern).into_iter().map(|node| node.full_path.as_str()).collect()
+    }
+
+    #[test]
+    fn glob_single_star() {
+        let root = sample_tree();
+        assert_eq!(
+            glob_paths(&root, "/avatar/parameters/*"),
+            [
+                "/avatar/parameters/GestureLeft",
+                "/avatar/parameters/GestureRight",
+                "/avatar/parameters/Voice"
+            ]
+        );
+        assert_eq!(glob_paths(&root, "/*/input"), ["/chatbox/input"]);
+        assert_eq!(glob_paths(&root, "/*"), ["/avatar", "/chatbox"]);
+        // Exactly one component, not zero
+        assert!(glob_paths(&root, "/avatar/change/*").is_empty());
+    }
+
+    #[test]
+    fn glob_double_star() {
+        let root = sample_tree();
+        assert_eq!(glob_paths(&root, "/**/Voice"), ["/avatar/parameters/Voice"]);
+        assert_eq!(
+            glob_paths(&root, "/avatar/**"),
+            [
+                "/avatar",
+                "/avatar/change",
+                "/avatar/parameters",
+                "/avatar/parameters/GestureLeft",
+                "/avatar/parameters/GestureRight",
+                "/avatar/parameters/Voice"
+            ]
+        );
+        assert_eq!(glob_paths(&root, "/**").len(), OscNode::node_count(&root));
+        // `**` can match nothing at all
+        assert_eq!(glob_paths(&root, "/avatar/**/change"), ["/avatar/change"]);
+    }
+
+    /// The built-in VRChat avatar parameters plus 100 custom toggles
+    #[cfg(feature = "msgpack")]
+    fn avatar_tree() -> OscNode {
+        let mut root = OscNode::new_container("/");
+        OscNode::add_method(&mut root, "/avatar/change", Access::Read, "s");
+        let builtin = [
+            ("GestureLeft", "i", json!([0])),
+            ("GestureRight", "i", json!([0])),
+            ("GestureLeftWeight", "f", json!([0.0])),
+            ("GestureRightWeight", "f", json!([0.0])),
+            ("VRCEmote", "i", json!([0])),
+            ("Grounded", "T", json!([true])),
+            ("Seated", "F", json!([false])),
+            ("AFK", "F", json!([false])),
+            ("Viseme", "i", json!([0])),
+            ("Voice", "f", json!([0.0])),
+        ];
+        for (name, typetag, value) in builtin {
+            let path = format!("/avatar/parameters/{}", name);
+            OscNode::add_method_with_value(&mut root, &path, Access::ReadWrite, typetag, value).unwrap();
+        }
+        for idx in 0..100 {
+            let (typetag, value) = match idx % 3 {
+                0 => ("T", json!([true])),
+                1 => ("i", json!([idx])),
+                _ => ("f", json!([0.5])),
+            };
+            let path = format!("/avatar/parameters/Toggle_{}", idx);
+            OscNode::add_method_with_value(&mut root, &path, Access::ReadWrite, typetag, value).unwrap();
+        }
+        root
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn msgpack_round_trip() {
+        let mut root = avatar_tree();
+        OscNode::add_overload(&mut root, "/avatar/change", "ss", Some("id and name"));
+        OscNode::get_node_mut(&mut root, "/avatar/parameters/Voice").unwrap().extended_type =
+            Some("volume".to_string());
+
+        let bytes = OscNode::to_msgpack(&root).unwrap();
+        let decoded = OscNode::from_msgpack(&bytes).unwrap();
+        assert_eq!(decoded, root);
+        let voice = OscNode::get_node(&decoded, "/avatar/parameters/Voice").unwrap();
+        assert_eq!(voice.full_path, "/avatar/parameters/Voice");
+
+        // Paths below a subtree are rebuilt from its own FULL_PATH
+        let parameters = OscNode::get_node(&root, "/avatar/parameters").unwrap();
+        let decoded = OscNode::from_msgpack(&OscNode::to_msgpack(parameters).unwrap()).unwrap();
+        assert_eq!(decoded.contents["Voice"].full_path, "/avatar/parameters/Voice");
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn msgpack_is_smaller_than_json() {
+        let root = avatar_tree();
+        let json = serde_json::to_vec(&root).unwrap().len();
+        let msgpack = OscNode::to_msgpack(&root).unwrap().len();
+
+        let saving = 1.0 - msgpack as f64 / json as f64;
+        assert!(saving >= 0.30, "{} bytes of MessagePack vs {} of JSON", msgpack, json);
+    }
+
+    fn hash(node: &OscNode) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        node.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn try_new_method_rejects_bad_typetags() {
+        for typetag in ["", "x", "[i", "i]"] {
+            assert_eq!(
+                OscNode::try_new_method("/a", Access::Read, typetag).unwrap_err(),
+                OscNodeError::InvalidTypetag(typetag.to_string()),
+            );
+        }
+    }
+
+    #[cfg(feature = "rosc")]
+    #[test]
+    fn register_from_osc_message_refuses_patterns() {
+        let mut root = OscNode::new_container("/");
+        let msg = |addr: &str| rosc::OscMessage {
+            addr: addr.to_string(),
+            args: vec![rosc::OscType::Float(1.0)],
+        };
+
+        OscNode::register_from_osc_message(&mut root, &msg("/a/b")).unwrap();
+        assert!(OscNode::register_from_osc_message(&mut root, &msg("/a/*")).is_err());
+        assert_eq!(OscNode::node_count(&root), 3);
+    }
+}
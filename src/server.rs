@@ -1,25 +1,183 @@
+use crate::access_log::{clf_line, AccessLog};
 use crate::client::discover_vrchat_oscquery;
-use crate::node::OscNode;
+use crate::node::{
+    check_value_matches_typetag, lenient_components, recompute_children,
+    validate_access_consistency_allowing, Access, OscNode, OscNodeError,
+};
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
-use hyper::{Request, Response, StatusCode};
-use hyper_util::rt::TokioIo;
-use mdns_sd::{ServiceDaemon, ServiceInfo};
-use serde::Serialize;
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper::header::{
+    HeaderName, HeaderValue, ACCEPT, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
+    ALLOW, CONTENT_LENGTH, HOST, RETRY_AFTER,
+};
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::{TokioIo, TokioTimer};
+use mdns_sd::{DaemonEvent, IfKind, ServiceDaemon, ServiceEvent, ServiceInfo};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::convert::Infallible;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
-use std::sync::{Arc, RwLock};
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Once};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::{broadcast, mpsc, watch, RwLock};
+use tokio::task::{AbortHandle, JoinHandle};
 use tokio::time::sleep;
 
 #[derive(Clone)]
 struct SharedState {
+    /// Async lock so serializing a large tree doesn't block a runtime thread.
+    /// Anything that mutates the tree must take the write lock with `.await`.
     root: Arc<RwLock<OscNode>>,
     host_info: Arc<HostInfo>,
+    pretty_json: bool,
+    response_headers: Arc<Vec<(HeaderName, HeaderValue)>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    access_policy: Arc<AccessPolicy>,
+    metrics: Arc<ServerMetrics>,
+    metrics_endpoint: bool,
+    /// `FULL_PATH` of the most recently changed value
+    value_changes: broadcast::Sender<String>,
+    listen_extension: bool,
+    /// Empty means any `Host` is accepted
+    allowed_hosts: Arc<Vec<String>>,
+    /// Refuse requests from anything but loopback addresses
+    local_only: bool,
+    request_logger: Option<RequestLogger>,
+    access_log: Option<AccessLog>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+type RequestLogger = Arc<dyn Fn(RequestLogEntry) + Send + Sync>;
+
+/// One handled request, passed to the `with_request_logger` callback
+#[derive(Debug, Clone)]
+pub struct RequestLogEntry {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub status: u16,
+    /// Time spent in the handler, not counting sending the body
+    pub duration_us: u64,
+    pub remote_addr: SocketAddr,
+}
+
+/// Commands queued in [`RunningServer::command_sender`] before senders wait
+const NODE_COMMAND_BUFFER: usize = 1024;
+
+/// How long a `?LISTEN` request waits for a change before giving up
+const LISTEN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Pause after a failed `accept`, so e.g. running out of file descriptors
+/// doesn't turn into a busy loop
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Changed paths a slow `?LISTEN` request can fall behind by before it just
+/// re-reads the node
+const VALUE_CHANGE_BUFFER: usize = 256;
+
+/// Counters behind the opt-in `?METRICS` endpoint
+struct ServerMetrics {
+    started: Instant,
+    requests: AtomicU64,
+    bytes_sent: AtomicU64,
+}
+
+impl ServerMetrics {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            requests: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "UPTIME_SECS": self.started.elapsed().as_secs(),
+            "REQUESTS_TOTAL": self.requests.load(Ordering::Relaxed),
+            "BYTES_SENT": self.bytes_sent.load(Ordering::Relaxed),
+        })
+    }
+}
+
+/// Decides which nodes a client gets to see in the tree and schema responses
+#[derive(Clone)]
+pub enum AccessPolicy {
+    /// Everything is visible
+    Open,
+    /// Only containers and nodes the client can read
+    ReadOnly,
+    /// Visible if the closure returns true for the node's `FULL_PATH`
+    Custom(AccessFilter),
+}
+
+/// Called with a node's `FULL_PATH` and the incoming request
+///
+/// Shared rather than boxed so builders holding a policy can be cloned.
+pub type AccessFilter = Arc<dyn Fn(&str, &Request<Incoming>) -> bool + Send + Sync>;
+
+impl AccessPolicy {
+    /// Filtered copy of `root`, or `None` if the policy hides nothing from `req`
+    ///
+    /// Callers serve `root` itself on `None`, so the tree is only copied
+    /// when something is actually hidden.
+    fn filter(&self, root: &OscNode, req: &Request<Incoming>) -> Option<OscNode> {
+        match self {
+            AccessPolicy::Open => None,
+            AccessPolicy::ReadOnly => OscNode::retained(root, |node| {
+                node.typetag.is_none()
+                    || matches!(node.access, Some(Access::Read | Access::ReadWrite))
+            }),
+            AccessPolicy::Custom(allow) => OscNode::retained(root, |node| allow(&node.full_path, req)),
+        }
+    }
+}
+
+/// Per-IP request counter, reset every second by a background task
+struct RateLimiter {
+    max_rps: u32,
+    counts: Mutex<HashMap<IpAddr, u32>>,
+}
+
+impl RateLimiter {
+    fn start(max_rps: u32) -> Arc<Self> {
+        let limiter = Arc::new(Self {
+            max_rps,
+            counts: Mutex::new(HashMap::new()),
+        });
+
+        // Only hold a weak reference so the task ends with the server
+        let weak = Arc::downgrade(&limiter);
+        tokio::task::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(1));
+            loop {
+                interval.tick().await;
+                match weak.upgrade() {
+                    Some(limiter) => limiter.counts.lock().unwrap().clear(),
+                    None => break,
+                }
+            }
+        });
+
+        limiter
+    }
+
+    /// Counts a request from `ip`, returning false once it is over the limit
+    fn allow(&self, ip: IpAddr) -> bool {
+        let mut counts = self.counts.lock().unwrap();
+        let count = counts.entry(ip).or_insert(0);
+        if *count >= self.max_rps {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HostInfo {
     #[serde(rename = "NAME")]
     pub name: String,
@@ -29,16 +187,126 @@ pub struct HostInfo {
     pub osc_port: u16,
     #[serde(rename = "OSC_TRANSPORT")]
     pub osc_transport: String,
-    #[serde(rename = "EXTENSIONS")]
+    #[serde(rename = "OSC_PORT_TCP", default, skip_serializing_if = "Option::is_none")]
+    pub osc_port_tcp: Option<u16>,
+    #[serde(rename = "EXTENSIONS", default = "empty_extensions")]
     pub extensions: serde_json::Value,
+    #[serde(rename = "DESCRIPTION", skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(rename = "VERSION", skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+fn empty_extensions() -> serde_json::Value {
+    serde_json::json!({})
+}
+
+/// Body of every error response, e.g. `{"ERROR":404,"DESCRIPTION":"path not found"}`
+///
+/// The spec doesn't define an error format, this just gives tooling a
+/// consistent shape to parse.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OscQueryErrorResponse {
+    /// HTTP status code
+    #[serde(rename = "ERROR")]
+    pub code: u16,
+    #[serde(rename = "DESCRIPTION")]
+    pub description: String,
+}
+
+/// Transport used by the advertised OSC endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OscTransport {
+    #[default]
+    Udp,
+    Tcp,
 }
 
+impl OscTransport {
+    /// Value advertised in `OSC_TRANSPORT` and the mDNS TXT record
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OscTransport::Udp => "UDP",
+            OscTransport::Tcp => "TCP",
+        }
+    }
+
+    /// mDNS service type the OSC endpoint is registered under
+    pub fn service_type(&self) -> &'static str {
+        match self {
+            OscTransport::Udp => "_osc._udp.local.",
+            OscTransport::Tcp => "_osc._tcp.local.",
+        }
+    }
+}
+
+/// Parts of the VRChat OSC namespace, see
+/// [`OscQueryServerBuilder::with_vrchat_namespaces`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VRChatNamespace {
+    /// `/avatar`, as in [`OscQueryServerBuilder::with_vrchat_avatar_receiver`]
+    Avatar,
+    /// `/tracking`, as in [`OscQueryServerBuilder::with_vrchat_tracking_receiver`]
+    Tracking,
+    /// `/input`, as in [`OscQueryServerBuilder::with_vrchat_input_receiver`]
+    Input,
+    /// `/chatbox`, as in [`OscQueryServerBuilder::with_vrchat_chatbox`]
+    Chatbox,
+    /// The `/debug` container
+    Debug,
+    /// The `/vrcft` container used by VRCFaceTracking
+    FaceTracking,
+}
+
+impl VRChatNamespace {
+    pub const ALL: &'static [VRChatNamespace] = &[
+        VRChatNamespace::Avatar,
+        VRChatNamespace::Tracking,
+        VRChatNamespace::Input,
+        VRChatNamespace::Chatbox,
+        VRChatNamespace::Debug,
+        VRChatNamespace::FaceTracking,
+    ];
+}
+
+/// Cloning gives an independent builder, handy for deriving variants from a
+/// common base
+#[derive(Clone)]
 pub struct OscQueryServerBuilder {
     app_name: String,
     bind_ip: IpAddr,
     http_port: u16,
+    http_port_range: Option<(u16, u16)>,
     osc_port: u16,
+    osc_port_range: Option<(u16, u16)>,
+    osc_transport: OscTransport,
+    osc_port_tcp: Option<u16>,
+    pretty_json: bool,
+    response_headers: Vec<(String, String)>,
+    max_depth: Option<usize>,
+    max_nodes: Option<usize>,
+    /// Containers opted into a non-standard `ACCESS` with `with_container_access`
+    allowed_container_paths: Vec<String>,
+    mdns_interface: Option<String>,
+    mdns_auto_restart: Option<Duration>,
+    service_properties: Vec<(String, String)>,
+    description: Option<String>,
+    version: Option<String>,
+    rate_limit: Option<u32>,
+    no_mdns: bool,
+    access_policy: AccessPolicy,
+    http_keep_alive: (bool, Duration),
+    metrics_endpoint: bool,
+    listen_extension: bool,
+    allowed_hosts: Vec<String>,
+    local_only: bool,
+    request_logger: Option<RequestLogger>,
+    access_log_file: Option<PathBuf>,
+    #[cfg(feature = "tls")]
+    tls: Option<(Vec<u8>, Vec<u8>)>,
     root: OscNode,
+    /// First failed `with_method_value`, reported by `build_and_run`
+    node_error: Option<OscNodeError>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -48,36 +316,369 @@ pub enum OscQueryServerError {
 
     #[error("IO error: {0}")]
     MdnsError(#[from] mdns_sd::Error),
+
+    #[error("Unsupported bind address {0}: only IPv4 is advertised over mDNS")]
+    UnsupportedBindAddress(IpAddr),
+
+    #[error("Invalid HTTP header name: {0:?}")]
+    InvalidHeaderName(String),
+
+    #[error("Invalid value for HTTP header {0}")]
+    InvalidHeaderValue(String),
+
+    #[error("Invalid app name {0:?}: must be 1-63 ASCII letters, digits or '-', not starting or ending with '-'")]
+    InvalidAppName(String),
+
+    #[error("Invalid mDNS TXT property key {0:?}: must be 1-253 printable ASCII characters other than '='")]
+    InvalidServiceProperty(String),
+
+    #[error("An mDNS service named {0:?} is already registered on this network")]
+    DuplicateServiceName(String),
+
+    #[error("Network interface not found: {0}")]
+    InterfaceNotFound(String),
+
+    #[error("Node tree is {depth} levels deep, more than the configured maximum of {max}")]
+    MaxDepthExceeded { depth: usize, max: usize },
+
+    #[cfg(feature = "tls")]
+    #[error("Invalid TLS certificate or key: {0}")]
+    InvalidTls(String),
+
+    #[error("No free port between {0} and {1}")]
+    NoFreePortInRange(u16, u16),
+
+    #[error("Invalid node: {0}")]
+    InvalidNode(#[from] OscNodeError),
+
+    #[error("Node tree has {count} methods, more than the configured maximum of {max}")]
+    NodeLimitExceeded { count: usize, max: usize },
 }
 
+const VRCHAT_INPUT_AXES: &[&str] = &[
+    "Vertical",
+    "Horizontal",
+    "LookHorizontal",
+    "UseAxisRight",
+    "GrabAxisRight",
+    "MoveHoldFB",
+    "SpinHoldCwCcw",
+    "SpinHoldUD",
+    "SpinHoldLR",
+];
+
+const VRCHAT_INPUT_BUTTONS: &[&str] = &[
+    "MoveForward",
+    "MoveBackward",
+    "MoveLeft",
+    "MoveRight",
+    "LookLeft",
+    "LookRight",
+    "Jump",
+    "Run",
+    "ComfortLeft",
+    "ComfortRight",
+    "DropRight",
+    "UseRight",
+    "GrabRight",
+    "DropLeft",
+    "UseLeft",
+    "GrabLeft",
+    "PanicButton",
+    "QuickMenuToggleLeft",
+    "QuickMenuToggleRight",
+    "Voice",
+];
+
 impl OscQueryServerBuilder {
+    /// `app_name` is used as the mDNS instance and host name, so it has to be
+    /// a valid DNS label: 1-63 ASCII letters, digits and `-`, not starting or
+    /// ending with `-`. This is checked in `build_and_run`.
     pub fn new(app_name: impl Into<String>, osc_port: u16) -> Self {
         Self {
             app_name: app_name.into(),
             bind_ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
             http_port: 0,
+            http_port_range: None,
             osc_port,
+            osc_port_range: None,
+            osc_transport: OscTransport::Udp,
+            osc_port_tcp: None,
+            pretty_json: false,
+            response_headers: Vec::new(),
+            max_depth: None,
+            max_nodes: None,
+            allowed_container_paths: Vec::new(),
+            mdns_interface: None,
+            mdns_auto_restart: None,
+            service_properties: Vec::new(),
+            description: None,
+            version: None,
+            rate_limit: None,
+            no_mdns: false,
+            access_policy: AccessPolicy::Open,
+            http_keep_alive: (true, Duration::from_secs(30)),
+            metrics_endpoint: false,
+            listen_extension: false,
+            allowed_hosts: Vec::new(),
+            local_only: false,
+            request_logger: None,
+            access_log_file: None,
+            #[cfg(feature = "tls")]
+            tls: None,
             root: OscNode::new_container("/"),
+            node_error: None,
         }
     }
 
+    /// IP address the HTTP server binds to
+    ///
+    /// Only IPv4 is supported, since the mDNS records are IPv4-only.
+    /// `build_and_run` rejects an IPv6 address instead of ignoring it.
     pub fn with_bind_ip(mut self, ip: IpAddr) -> Self {
         self.bind_ip = ip;
         self
     }
 
+    /// Bind to `127.0.0.1` and answer only loopback clients
+    ///
+    /// Requests from any other address get `403 Forbidden`, even if a later
+    /// `with_bind_ip` opens the server up to the network.
+    pub fn with_local_only(mut self) -> Self {
+        self.bind_ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        self.local_only = true;
+        self
+    }
+
     pub fn with_http_port(mut self, port: u16) -> Self {
         self.http_port = port;
         self
     }
 
+    /// Bind the first free HTTP port in `start..=end`
+    ///
+    /// For when firewall rules need a predictable port but a fixed one
+    /// might be taken. Overrides `with_http_port`.
+    pub fn with_http_port_range(mut self, start: u16, end: u16) -> Self {
+        self.http_port_range = Some((start, end));
+        self
+    }
+
+    /// Configure HTTP/1.1 keep-alive, on with a 30 second idle timeout by default
+    ///
+    /// `idle_timeout` bounds how long a connection may wait for the next
+    /// request's headers. Disabling keep-alive saves the memory of idle
+    /// connections at the cost of a new connection for every request.
+    pub fn with_http_keep_alive(mut self, enabled: bool, idle_timeout: Duration) -> Self {
+        self.http_keep_alive = (enabled, idle_timeout);
+        self
+    }
+
+    /// Free-form description shown by some OSCQuery clients
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Application version, advertised as `VERSION` in `HOST_INFO`
+    pub fn with_version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
+    /// Advertise the OSC endpoint over the given transport
+    ///
+    /// Defaults to UDP, which is what VRChat itself uses. TCP changes both
+    /// `OSC_TRANSPORT` and the mDNS service type to `_osc._tcp.local.`.
+    pub fn with_osc_transport(mut self, transport: OscTransport) -> Self {
+        self.osc_transport = transport;
+        self
+    }
+
+    /// Also advertise an OSC-over-TCP port next to the UDP one
+    ///
+    /// Registers an extra `_osc._tcp.local.` service and adds `OSC_PORT_TCP`
+    /// to `HOST_INFO`. The extra registration is skipped when the main
+    /// transport is already TCP.
+    pub fn with_osc_port_tcp(mut self, port: u16) -> Self {
+        self.osc_port_tcp = Some(port);
+        self
+    }
+
+    /// Pick the first free OSC port in `start..=end` instead of a fixed one
+    ///
+    /// Each port is checked by briefly binding it on the bind IP with the
+    /// configured transport. The chosen port is available from
+    /// [`RunningServer::osc_port`]; bind your OSC socket to it straight away.
+    pub fn with_osc_port_range(mut self, start: u16, end: u16) -> Self {
+        self.osc_port_range = Some((start, end));
+        self
+    }
+
+    /// Pretty-print JSON responses, handy when poking the server with `curl`
+    ///
+    /// This roughly triples the response size, so leave it off in production.
+    pub fn with_pretty_json(mut self, pretty: bool) -> Self {
+        self.pretty_json = pretty;
+        self
+    }
+
+    /// Add a header to every HTTP response
+    ///
+    /// Names must be valid RFC 7230 tokens; this is checked in `build_and_run`.
+    pub fn with_response_header(
+        mut self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.response_headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Only announce over mDNS on the named interface (e.g. `"eth0"`)
+    ///
+    /// Useful with VPNs or multiple NICs. Loopback stays enabled so VRChat
+    /// on the same machine can still find the service.
+    pub fn with_mdns_interface(mut self, ifname: impl Into<String>) -> Self {
+        self.mdns_interface = Some(ifname.into());
+        self
+    }
+
+    /// Check the mDNS daemon every `retry_interval` and replace it if it died
+    ///
+    /// The daemon's sockets can go stale when an interface goes down and
+    /// comes back, e.g. after a laptop suspends. The check is a test browse;
+    /// if that fails, a new daemon is created and every service is
+    /// registered again.
+    pub fn with_mdns_auto_restart(mut self, retry_interval: Duration) -> Self {
+        self.mdns_auto_restart = Some(retry_interval);
+        self
+    }
+
+    /// Add a TXT record property to the advertised mDNS services
+    ///
+    /// Added to both the OSCQuery and OSC services, after the built-in
+    /// `name`, `osc_port` and `osc_transport`, so the same key overrides
+    /// those. Keys must be printable ASCII without `=`; this is checked in
+    /// `build_and_run`. DNS-SD limits each `key=value` entry to 255 bytes,
+    /// so longer values are truncated with a warning.
+    pub fn with_service_property(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.service_properties.push((key.into(), value.into()));
+        self
+    }
+
+    /// Answer with `429 Too Many Requests` once a client IP makes more
+    /// than `max_rps` requests within a second
+    pub fn with_rate_limit(mut self, max_rps: u32) -> Self {
+        self.rate_limit = Some(max_rps);
+        self
+    }
+
+    /// Serve OSCQuery over HTTP only, without any mDNS
+    ///
+    /// For environments without multicast (containers, cloud VMs) where
+    /// clients connect to a known address. Skips service registration and
+    /// VRChat discovery entirely.
+    pub fn with_no_mdns(mut self) -> Self {
+        self.no_mdns = true;
+        self
+    }
+
+    /// Hide nodes from clients according to `policy`, defaults to [`AccessPolicy::Open`]
+    pub fn with_access_control(mut self, policy: AccessPolicy) -> Self {
+        self.access_policy = policy;
+        self
+    }
+
+    /// Serve uptime, request and byte counters as JSON at `?METRICS`
+    pub fn with_metrics_endpoint(mut self) -> Self {
+        self.metrics_endpoint = true;
+        self
+    }
+
+    /// Serve HTTPS instead of plain HTTP, from PEM-encoded certificate chain and key
+    ///
+    /// mDNS advertises the same port as without TLS. Clients have to be told
+    /// to use `https://` themselves, and with a self-signed certificate they
+    /// also need to disable certificate verification. VRChat only speaks
+    /// plain HTTP, so this is for other OSCQuery clients.
+    #[cfg(feature = "tls")]
+    pub fn with_tls(mut self, cert_pem: Vec<u8>, key_pem: Vec<u8>) -> Self {
+        self.tls = Some((cert_pem, key_pem));
+        self
+    }
+
+    /// Only answer requests whose `Host` header is one of `hosts`
+    ///
+    /// Entries match with or without the port, ignoring case, so
+    /// `localhost` allows `localhost:8080`. Anything else gets
+    /// `400 Bad Request`, which protects against DNS rebinding. An empty
+    /// list (the default) accepts any host.
+    pub fn with_allowed_hosts(mut self, hosts: Vec<String>) -> Self {
+        self.allowed_hosts = hosts;
+        self
+    }
+
+    /// Call `logger` after every request, e.g. to feed your own access log
+    ///
+    /// Runs on the connection's task before the response is sent, so keep
+    /// it quick.
+    pub fn with_request_logger(mut self, logger: impl Fn(RequestLogEntry) + Send + Sync + 'static) -> Self {
+        self.request_logger = Some(Arc::new(logger));
+        self
+    }
+
+    /// Append a Common Log Format line for every request to `path`
+    ///
+    /// Lines are written by a background task. Once the file would grow
+    /// past 100 MB it is renamed with a `.YYYYMMDD-HHMMSS` suffix and a
+    /// fresh one is started. Failing to open the file makes `build_and_run`
+    /// return [`OscQueryServerError::ListenError`].
+    pub fn with_access_log_file(mut self, path: impl AsRef<Path>) -> Self {
+        self.access_log_file = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Let clients long-poll for value changes with `GET /?LISTEN=/some/path`
+    ///
+    /// The request is held until [`RunningServer::set_value`] changes that
+    /// path, then answered with the node's JSON. After 30 seconds without a
+    /// change it is answered with `204 No Content` and the client should
+    /// ask again. The path may be percent-encoded and may end in `/`.
+    /// Meant for clients that can't use WebSockets.
+    pub fn with_listen_extension(mut self) -> Self {
+        self.listen_extension = true;
+        self
+    }
+
+    /// Refuse to start if the node tree is nested deeper than `depth`
+    ///
+    /// Some OSCQuery clients reject trees beyond a certain depth. Edits to
+    /// the running server that would go deeper are rejected as well.
+    pub fn with_max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Refuse to start if the tree has more than `limit` methods
+    ///
+    /// Containers don't count towards the limit. Guards against a runaway
+    /// caller producing a huge, slow to serialize tree. Edits to the running
+    /// server that would go over the limit are rejected as well.
+    pub fn with_max_nodes(mut self, limit: usize) -> Self {
+        self.max_nodes = Some(limit);
+        self
+    }
+
     /// Receive all VRChat avatar parameters
     ///
-    /// This makes sure `/avatar` exists so VRChat will auto-route
-    /// `/avatar/change` and `/avatar/parameters/*` to your OSC port.
+    /// Advertises `/avatar/change` and the `/avatar/parameters` container
+    /// so VRChat will auto-route avatar changes and `/avatar/parameters/*`
+    /// to your OSC port.
     pub fn with_vrchat_avatar_receiver(mut self) -> Self {
-        // Ensure /avatar container exists
-        OscNode::ensure_path(&mut self.root, "/avatar");
+        OscNode::ensure_path(&mut self.root, "/avatar/parameters");
+        // Sent with the new avatar's ID whenever the user switches avatars
+        OscNode::add_method(&mut self.root, "/avatar/change", Access::Read, "s");
         self
     }
 
@@ -87,10 +688,190 @@ impl OscQueryServerBuilder {
         self
     }
 
-    pub async fn build_and_run(self) -> Result<RunningServer, OscQueryServerError> {
+    /// Advertise VRChat's `/input` controls (axes and buttons)
+    ///
+    /// Axes take a float in `-1..=1`, buttons take an int `1` (pressed)
+    /// or `0` (released). The list follows the "OSC as Input Controller"
+    /// page of the VRChat docs as of VRChat 2022.1.1, the release that
+    /// introduced OSC input.
+    pub fn with_vrchat_input_receiver(mut self) -> Self {
+        OscNode::ensure_path(&mut self.root, "/input");
+        for axis in VRCHAT_INPUT_AXES {
+            OscNode::add_method(&mut self.root, &format!("/input/{}", axis), Access::Write, "f");
+        }
+        for button in VRCHAT_INPUT_BUTTONS {
+            OscNode::add_method(&mut self.root, &format!("/input/{}", button), Access::Write, "i");
+        }
+        self
+    }
+
+    /// Advertise VRChat's chatbox endpoints
+    ///
+    /// Per the [VRChat OSC chatbox docs](https://docs.vrchat.com/docs/osc-as-input-controller#chatbox):
+    /// - `/chatbox/input`, typetag `sT`: the message text, then a bool that
+    ///   sends it straight away instead of opening the keyboard. VRChat also
+    ///   takes an optional third bool to skip the notification sound, which
+    ///   isn't advertised.
+    /// - `/chatbox/typing`, typetag `T`: turns the typing indicator on or off
+    ///
+    /// OSC has no bool typetag, only the value-carrying `T` and `F`. As in
+    /// VRChat's own OSCQuery tree, `T` is advertised for a bool of either
+    /// value, and [`check_value_matches_typetag`] accepts both for it.
+    pub fn with_vrchat_chatbox(mut self) -> Self {
+        OscNode::add_method(&mut self.root, "/chatbox/input", Access::Write, "sT");
+        OscNode::add_method(&mut self.root, "/chatbox/typing", Access::Write, "T");
+        self
+    }
+
+    /// Everything VRChat sends or accepts over OSC, see [`VRChatNamespace`]
+    pub fn with_vrchat_all(self) -> Self {
+        self.with_vrchat_namespaces(VRChatNamespace::ALL)
+    }
+
+    /// Register only the given parts of the VRChat namespace
+    pub fn with_vrchat_namespaces(mut self, namespaces: &[VRChatNamespace]) -> Self {
+        for namespace in namespaces {
+            self = match namespace {
+                VRChatNamespace::Avatar => self.with_vrchat_avatar_receiver(),
+                VRChatNamespace::Tracking => self.with_vrchat_tracking_receiver(),
+                VRChatNamespace::Input => self.with_vrchat_input_receiver(),
+                VRChatNamespace::Chatbox => self.with_vrchat_chatbox(),
+                VRChatNamespace::Debug => {
+                    OscNode::ensure_path(&mut self.root, "/debug");
+                    self
+                }
+                VRChatNamespace::FaceTracking => {
+                    OscNode::ensure_path(&mut self.root, "/vrcft");
+                    self
+                }
+            };
+        }
+        self
+    }
+
+    /// Advertise a method with an initial `VALUE`
+    ///
+    /// `value` is checked against `typetag`; a mismatch makes
+    /// `build_and_run` fail with [`OscQueryServerError::InvalidNode`].
+    pub fn with_method_value(
+        mut self,
+        path: &str,
+        access: Access,
+        typetag: &str,
+        value: serde_json::Value,
+    ) -> Self {
+        if let Err(err) = OscNode::add_method_with_value(&mut self.root, path, access, typetag, value) {
+            self.node_error.get_or_insert(err);
+        }
+        self
+    }
+
+    /// Advertise the container at `path` with a non-standard `ACCESS`
+    ///
+    /// The spec wants `ACCESS` 0 on every container, and `build_and_run`
+    /// warns about any that aren't. Only the container at `path` is
+    /// accepted without a warning, others with the same access are still
+    /// flagged; see [`validate_access_consistency_allowing`].
+    pub fn with_container_access(mut self, path: &str, access: Access) -> Self {
+        let container = OscNode::ensure_path(&mut self.root, path);
+        container.access = Some(access);
+        if !self.allowed_container_paths.contains(&container.full_path) {
+            self.allowed_container_paths.push(container.full_path.clone());
+        }
+        self
+    }
+
+    /// The node tree as it will be served, for checking before `build_and_run`
+    pub fn inspect_tree(&self) -> &OscNode {
+        &self.root
+    }
+
+    /// [`Self::inspect_tree`] as JSON, pretty-printed if `with_pretty_json` is on
+    pub fn inspect_tree_json(&self) -> Result<String, serde_json::Error> {
+        if self.pretty_json {
+            serde_json::to_string_pretty(&self.root)
+        } else {
+            serde_json::to_string(&self.root)
+        }
+    }
+
+    pub async fn build_and_run(mut self) -> Result<RunningServer, OscQueryServerError> {
+        if !is_dns_label(&self.app_name) {
+            return Err(OscQueryServerError::InvalidAppName(self.app_name));
+        }
+
+        if let Some(err) = self.node_error.take() {
+            return Err(err.into());
+        }
+
+        if self.bind_ip.is_ipv6() {
+            return Err(OscQueryServerError::UnsupportedBindAddress(self.bind_ip));
+        }
+
+        if let Some(max) = self.max_depth {
+            let depth = OscNode::max_depth(&self.root);
+            if depth > max {
+                return Err(OscQueryServerError::MaxDepthExceeded { depth, max });
+            }
+        }
+
+        for path in validate_access_consistency_allowing(&self.root, &self.allowed_container_paths) {
+            log::warn!("Container {} has an ACCESS other than None", path);
+        }
+
+        if let Some(max) = self.max_nodes {
+            let count = OscNode::find(&self.root, |node| node.typetag.is_some()).len();
+            if count > max {
+                return Err(OscQueryServerError::NodeLimitExceeded { count, max });
+            }
+        }
+
+        if let Some((key, _)) = self
+            .service_properties
+            .iter()
+            .find(|(key, _)| !is_txt_key(key))
+        {
+            return Err(OscQueryServerError::InvalidServiceProperty(key.clone()));
+        }
+
+        if let Some(ifname) = &self.mdns_interface {
+            let interfaces = if_addrs::get_if_addrs()?;
+            if !interfaces.iter().any(|interface| &interface.name == ifname) {
+                return Err(OscQueryServerError::InterfaceNotFound(ifname.clone()));
+            }
+        }
+
+        let mut response_headers = Vec::with_capacity(self.response_headers.len());
+        for (name, value) in &self.response_headers {
+            let header_name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| OscQueryServerError::InvalidHeaderName(name.clone()))?;
+            let header_value = HeaderValue::from_str(value)
+                .map_err(|_| OscQueryServerError::InvalidHeaderValue(name.clone()))?;
+            response_headers.push((header_name, header_value));
+        }
+
+        if let Some((start, end)) = self.osc_port_range {
+            self.osc_port = (start..=end)
+                .find(|&port| self.osc_port_free(port))
+                .ok_or(OscQueryServerError::NoFreePortInRange(start, end))?;
+        }
+
+        #[cfg(feature = "tls")]
+        let tls_acceptor = match &self.tls {
+            Some((cert_pem, key_pem)) => Some(tls_acceptor(cert_pem, key_pem)?),
+            None => None,
+        };
+
         // Bind HTTP
-        let http_listener =
-            tokio::net::TcpListener::bind(SocketAddr::new(self.bind_ip, self.http_port)).await?;
+        let http_listener = match self.http_port_range {
+            Some((start, end)) => self
+                .bind_http_in_range(start, end)
+                .await
+                .ok_or(OscQueryServerError::NoFreePortInRange(start, end))?,
+            None => {
+                tokio::net::TcpListener::bind(SocketAddr::new(self.bind_ip, self.http_port)).await?
+            }
+        };
         let local_addr = http_listener.local_addr()?;
         let http_port = local_addr.port();
 
@@ -103,40 +884,163 @@ impl OscQueryServerBuilder {
             name: self.app_name.clone(),
             osc_ip: self.bind_ip.to_string(),
             osc_port: self.osc_port,
-            osc_transport: "UDP".to_string(),
+            osc_transport: self.osc_transport.as_str().to_string(),
+            osc_port_tcp: self.osc_port_tcp,
             extensions: serde_json::json!({}), // no extensions yet
+            description: self.description.clone(),
+            version: self.version.clone(),
         };
 
+        // Leave a placeholder behind so `self` is still usable for mDNS below
+        let root = Arc::new(RwLock::new(std::mem::replace(
+            &mut self.root,
+            OscNode::new_container("/"),
+        )));
+        let access_log = match &self.access_log_file {
+            Some(path) => Some(AccessLog::open(path).await?),
+            None => None,
+        };
+
+        let (value_changes, _) = broadcast::channel(VALUE_CHANGE_BUFFER);
         let state = SharedState {
-            root: Arc::new(RwLock::new(self.root)),
+            root: root.clone(),
             host_info: Arc::new(host_info),
+            pretty_json: self.pretty_json,
+            response_headers: Arc::new(response_headers),
+            rate_limiter: self.rate_limit.map(RateLimiter::start),
+            access_policy: Arc::new(std::mem::replace(&mut self.access_policy, AccessPolicy::Open)),
+            metrics: Arc::new(ServerMetrics::new()),
+            metrics_endpoint: self.metrics_endpoint,
+            value_changes: value_changes.clone(),
+            listen_extension: self.listen_extension,
+            allowed_hosts: Arc::new(std::mem::take(&mut self.allowed_hosts)),
+            local_only: self.local_only,
+            request_logger: self.request_logger.take(),
+            access_log: access_log.clone(),
         };
 
-        tokio::task::spawn(async move {
+        let (keep_alive, idle_timeout) = self.http_keep_alive;
+        let http_task = tokio::task::spawn(async move {
             loop {
                 let shared = state.clone();
 
-                let (stream, _) = http_listener.accept().await.unwrap();
+                // Errors like EMFILE are usually transient, so keep going
+                // until the task is aborted
+                let (stream, remote_addr) = match http_listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(err) => {
+                        log::warn!("Error accepting connection: {:?}", err);
+                        tokio::time::sleep(ACCEPT_ERROR_BACKOFF).await;
+                        continue;
+                    }
+                };
 
-                // Use an adapter to access something implementing `tokio::io` traits as if they implement
-                // `hyper::rt` IO traits.
-                let io = TokioIo::new(stream);
+                #[cfg(feature = "tls")]
+                let tls_acceptor = tls_acceptor.clone();
 
                 // Spawn a tokio task to serve multiple connections concurrently
                 tokio::task::spawn(async move {
-                    // Finally, we bind the incoming connection to our `hello` service
-                    if let Err(err) = http1::Builder::new()
-                        // `service_fn` converts our function in a `Service`
-                        .serve_connection(io, service_fn(|req| handle_request(req, shared.clone())))
-                        .await
-                    {
-                        eprintln!("Error serving connection: {:?}", err);
+                    #[cfg(feature = "tls")]
+                    if let Some(acceptor) = tls_acceptor {
+                        match acceptor.accept(stream).await {
+                            Ok(stream) => {
+                                serve_connection(stream, remote_addr, shared, keep_alive, idle_timeout).await
+                            }
+                            Err(err) => log::warn!("TLS handshake failed: {:?}", err),
+                        }
+                        return;
                     }
+
+                    serve_connection(stream, remote_addr, shared, keep_alive, idle_timeout).await;
                 });
             }
         });
 
-        let mdns = ServiceDaemon::new()?;
+        let generation = Arc::new(AtomicU64::new(0));
+        let limits = TreeLimits {
+            max_depth: self.max_depth,
+            max_nodes: self.max_nodes,
+        };
+        let commands =
+            spawn_command_task(root.clone(), limits, value_changes.clone(), generation.clone());
+
+        let (mdns, services) = if self.no_mdns {
+            (None, Vec::new())
+        } else {
+            match self.advertise(http_port).await {
+                Ok((mdns, services)) => (Some(Arc::new(Mutex::new(mdns))), services),
+                Err(err) => {
+                    http_task.abort();
+                    return Err(err);
+                }
+            }
+        };
+        let service_names = services.iter().map(|info| info.get_fullname().to_string()).collect();
+
+        let mdns_watchdog = match (&mdns, self.mdns_auto_restart) {
+            (Some(mdns), Some(interval)) => Some(
+                tokio::task::spawn(mdns_watchdog(
+                    mdns.clone(),
+                    services,
+                    self.mdns_interface.clone(),
+                    interval,
+                ))
+                .abort_handle(),
+            ),
+            _ => None,
+        };
+
+        let http_abort = http_task.abort_handle();
+        let (stopped_sender, stopped) = watch::channel(false);
+        tokio::task::spawn(finish_shutdown(
+            http_task,
+            mdns.clone(),
+            mdns_watchdog,
+            service_names,
+            access_log,
+            stopped_sender,
+        ));
+
+        Ok(RunningServer {
+            _mdns: mdns,
+            root,
+            local_addr,
+            osc_port: self.osc_port,
+            value_changes,
+            generation,
+            limits,
+            commands,
+            http_abort,
+            stopped,
+        })
+    }
+
+    async fn bind_http_in_range(&self, start: u16, end: u16) -> Option<tokio::net::TcpListener> {
+        for port in start..=end {
+            if let Ok(listener) = tokio::net::TcpListener::bind(SocketAddr::new(self.bind_ip, port)).await {
+                return Some(listener);
+            }
+        }
+        None
+    }
+
+    fn osc_port_free(&self, port: u16) -> bool {
+        let addr = SocketAddr::new(self.bind_ip, port);
+        match self.osc_transport {
+            OscTransport::Udp => std::net::UdpSocket::bind(addr).is_ok(),
+            OscTransport::Tcp => std::net::TcpListener::bind(addr).is_ok(),
+        }
+    }
+
+    /// Registers the OSCQuery and OSC services over mDNS, then waits for VRChat
+    ///
+    /// Returns the daemon along with the registered services.
+    async fn advertise(
+        &self,
+        http_port: u16,
+    ) -> Result<(ServiceDaemon, Vec<ServiceInfo>), OscQueryServerError> {
+        let mut mdns = DaemonGuard::new(create_daemon(self.mdns_interface.as_deref())?);
+        let mdns_events = mdns.daemon.monitor()?;
 
 
         let service_type_oscquery = "_oscjson._tcp.local.";
@@ -147,8 +1051,16 @@ impl OscQueryServerBuilder {
         let mut props_oscquery = HashMap::new();
         props_oscquery.insert("name".to_string(), self.app_name.clone());
         props_oscquery.insert("osc_port".to_string(), self.osc_port.to_string());
-        props_oscquery.insert("osc_transport".to_string(), "UDP".to_string());
+        props_oscquery.insert(
+            "osc_transport".to_string(),
+            self.osc_transport.as_str().to_string(),
+        );
+        props_oscquery.extend(self.service_properties.iter().cloned());
+        sanitize_txt_properties(&mut props_oscquery)?;
 
+        // Records use mdns_sd's RFC 6762 TTLs (120s for SRV/A, 4500s for
+        // PTR/TXT). mdns_sd keeps the TTL setters private, so these can't be
+        // made configurable from here yet.
         let info_oscquery = ServiceInfo::new(
             service_type_oscquery,
             &self.app_name,
@@ -158,13 +1070,16 @@ impl OscQueryServerBuilder {
             props_oscquery,
         )?;
 
+        let mut services = vec![info_oscquery.clone()];
         mdns.register(info_oscquery)?;
 
 
-        let service_type_osc = "_osc._udp.local.";
+        let service_type_osc = self.osc_transport.service_type();
 
         let mut props_osc = HashMap::new();
         props_osc.insert("name".to_string(), self.app_name.clone());
+        props_osc.extend(self.service_properties.iter().cloned());
+        sanitize_txt_properties(&mut props_osc)?;
 
         let info_osc = ServiceInfo::new(
             service_type_osc,
@@ -175,44 +1090,906 @@ impl OscQueryServerBuilder {
             props_osc,
         )?;
 
+        services.push(info_osc.clone());
         mdns.register(info_osc)?;
 
+        // A TCP port next to the main transport; if that's already TCP
+        // there is nothing extra to register
+        if let (Some(port), OscTransport::Udp) = (self.osc_port_tcp, self.osc_transport) {
+            let mut props_osc_tcp = HashMap::new();
+            props_osc_tcp.insert("name".to_string(), self.app_name.clone());
+            props_osc_tcp.extend(self.service_properties.iter().cloned());
+            sanitize_txt_properties(&mut props_osc_tcp)?;
+
+            let info_osc_tcp = ServiceInfo::new(
+                OscTransport::Tcp.service_type(),
+                &self.app_name,
+                &host_name,
+                IpAddr::V4(addr_ipv4),
+                port,
+                props_osc_tcp,
+            )?;
+
+            services.push(info_osc_tcp.clone());
+            mdns.register(info_osc_tcp)?;
+        }
+
 
         // For some reason we need to wait and then query the mDNS service for VRChat to find it...?
         sleep(Duration::from_secs(1)).await;
 
-        discover_vrchat_oscquery(Duration::from_secs(5)).await.unwrap();
+        // mdns_sd doesn't fail on a name conflict, it renames our records
+        // instead. Treat that as another instance already using `app_name`.
+        while let Ok(event) = mdns_events.try_recv() {
+            if let DaemonEvent::NameChange(change) = event {
+                let ours = services.iter().any(|info| info.get_fullname() == change.original);
+                if change.original == host_name || ours {
+                    return Err(OscQueryServerError::DuplicateServiceName(self.app_name.clone()));
+                }
+            }
+        }
+
+        // Only there to make VRChat notice us; not finding it is fine, e.g.
+        // when it isn't running yet or several servers start at once
+        if let Err(err) = discover_vrchat_oscquery(Duration::from_secs(5)).await {
+            log::debug!("VRChat OSCQuery service not found after advertising: {}", err);
+        }
 
-        Ok(RunningServer { _mdns: mdns })
+        Ok((mdns.defuse(), services))
+    }
+}
+
+/// Shuts the daemon down when dropped, unless [`Self::defuse`]d
+///
+/// Keeps an early return from `advertise` from leaving the daemon thread
+/// running, or the services registered so far advertised.
+struct DaemonGuard {
+    daemon: ServiceDaemon,
+    registered: Vec<String>,
+    armed: bool,
+}
+
+impl DaemonGuard {
+    fn new(daemon: ServiceDaemon) -> Self {
+        Self {
+            daemon,
+            registered: Vec::new(),
+            armed: true,
+        }
+    }
+
+    fn register(&mut self, info: ServiceInfo) -> Result<(), mdns_sd::Error> {
+        let name = info.get_fullname().to_string();
+        self.daemon.register(info)?;
+        self.registered.push(name);
+        Ok(())
+    }
+
+    /// Hands over the daemon, leaving everything registered
+    fn defuse(mut self) -> ServiceDaemon {
+        self.armed = false;
+        self.daemon.clone()
+    }
+}
+
+impl Drop for DaemonGuard {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        // Queued before the shutdown, so the goodbye packets still go out
+        for name in &self.registered {
+            self.daemon.unregister(name).ok();
+        }
+        self.daemon.shutdown().ok();
     }
 }
 
+/// `ServiceDaemon::new`, limited to `interface` (plus loopback) if given
+fn create_daemon(interface: Option<&str>) -> Result<ServiceDaemon, mdns_sd::Error> {
+    let mdns = ServiceDaemon::new()?;
+    if let Some(ifname) = interface {
+        let limited = mdns.disable_interface(IfKind::All).and_then(|()| {
+            mdns.enable_interface(vec![IfKind::Name(ifname.to_string()), IfKind::LoopbackV4])
+        });
+        if let Err(err) = limited {
+            mdns.shutdown().ok();
+            return Err(err);
+        }
+    }
+    Ok(mdns)
+}
+
+/// How long the watchdog's test browse may take to start
+const MDNS_PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Replaces the daemon behind `mdns` whenever it stops answering
+async fn mdns_watchdog(
+    mdns: Arc<Mutex<ServiceDaemon>>,
+    services: Vec<ServiceInfo>,
+    interface: Option<String>,
+    interval: Duration,
+) {
+    loop {
+        sleep(interval).await;
+
+        let daemon = mdns.lock().unwrap().clone();
+        if mdns_responds(&daemon).await {
+            continue;
+        }
+
+        log::warn!("mDNS daemon stopped responding, restarting it");
+        let restarted = create_daemon(interface.as_deref()).and_then(|daemon| {
+            for info in &services {
+                daemon.register(info.clone())?;
+            }
+            Ok(daemon)
+        });
+        match restarted {
+            Ok(new) => {
+                let old = std::mem::replace(&mut *mdns.lock().unwrap(), new);
+                old.shutdown().ok();
+            }
+            Err(err) => log::warn!("Failed to restart mDNS daemon: {}", err),
+        }
+    }
+}
+
+/// Test browse: the daemon is fine if it starts a search in time
+async fn mdns_responds(mdns: &ServiceDaemon) -> bool {
+    let service_type = "_oscjson._tcp.local.";
+    let Ok(receiver) = mdns.browse(service_type) else {
+        return false;
+    };
+
+    let started = tokio::time::timeout(MDNS_PING_TIMEOUT, async {
+        while let Ok(event) = receiver.recv_async().await {
+            if matches!(event, ServiceEvent::SearchStarted(_)) {
+                return true;
+            }
+        }
+        false
+    })
+    .await
+    .unwrap_or(false);
+
+    mdns.stop_browse(service_type).ok();
+    started
+}
+
 async fn handle_request(
-    req: Request<hyper::body::Incoming>,
+    req: Request<Incoming>,
+    remote_addr: SocketAddr,
     state: SharedState,
-) -> Result<Response<String>, Infallible> {
+) -> Result<Response<ResponseBody>, Infallible> {
+    let logger = state.request_logger.clone();
+    let access_log = state.access_log.clone();
+    if logger.is_none() && access_log.is_none() {
+        return respond(req, remote_addr.ip(), state).await;
+    }
+
+    let started = Instant::now();
+    let received = SystemTime::now();
+    let method = req.method().to_string();
+    let path = req.uri().path().to_string();
+    let query = req.uri().query().map(|query| query.to_string());
+    let version = req.version();
+
+    let response = respond(req, remote_addr.ip(), state).await?;
+    let status = response.status().as_u16();
+
+    if let Some(access_log) = access_log {
+        let target = match &query {
+            Some(query) => format!("{}?{}", path, query),
+            None => path.clone(),
+        };
+        let bytes = response
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|length| length.to_str().ok()?.parse().ok());
+        let request_line = format!("{} {} {:?}", method, target, version);
+        access_log
+            .log(clf_line(remote_addr.ip(), received, &request_line, status, bytes))
+            .await;
+    }
+    if let Some(logger) = logger {
+        logger(RequestLogEntry {
+            method,
+            path,
+            query,
+            status,
+            duration_us: started.elapsed().as_micros() as u64,
+            remote_addr,
+        });
+    }
+    Ok(response)
+}
+
+async fn respond(
+    req: Request<Incoming>,
+    remote_ip: IpAddr,
+    state: SharedState,
+) -> Result<Response<ResponseBody>, Infallible> {
+    state.metrics.requests.fetch_add(1, Ordering::Relaxed);
+
+    if state.local_only && !remote_ip.is_loopback() {
+        return Ok(error_response(&state, StatusCode::FORBIDDEN, "local connections only"));
+    }
+
+    if let Some(limiter) = &state.rate_limiter {
+        if !limiter.allow(remote_ip) {
+            let mut response =
+                error_response(&state, StatusCode::TOO_MANY_REQUESTS, "rate limit exceeded");
+            response.headers_mut().insert(RETRY_AFTER, HeaderValue::from_static("1"));
+            return Ok(response);
+        }
+    }
+
+    if !host_allowed(&state.allowed_hosts, &req) {
+        return Ok(error_response(&state, StatusCode::BAD_REQUEST, "host not allowed"));
+    }
+
+    match *req.method() {
+        Method::GET => {}
+        Method::OPTIONS => return Ok(preflight_response(&state)),
+        _ => {
+            let mut response =
+                error_response(&state, StatusCode::METHOD_NOT_ALLOWED, "method not allowed");
+            response.headers_mut().insert(ALLOW, HeaderValue::from_static(ALLOWED_METHODS));
+            return Ok(response);
+        }
+    }
+
     let uri = req.uri();
     let query = uri.query().unwrap_or("");
 
-    if query.eq_ignore_ascii_case("HOST_INFO") {
-        let json = serde_json::to_string(&*state.host_info).unwrap_or_else(|_| "".to_string());
-        return Ok(Response::builder()
-            .status(StatusCode::OK)
-            .header("Content-Type", "application/json")
-            .body(json)
-            .unwrap());
+    // Some clients ask for `/HOST_INFO` rather than the spec's `/?HOST_INFO`
+    let host_info_path = uri.path().trim_start_matches('/').eq_ignore_ascii_case("HOST_INFO");
+    if host_info_path {
+        // A client doing this does it on every request, so only the first is a warning
+        static WARNED: Once = Once::new();
+        let mut first = false;
+        WARNED.call_once(|| first = true);
+        if first {
+            log::warn!("HOST_INFO requested as a path; clients should use the `?HOST_INFO` query instead");
+        } else {
+            log::debug!("HOST_INFO requested as a path");
+        }
+    }
+
+    if query.eq_ignore_ascii_case("HOST_INFO") || host_info_path {
+        let json = to_json(&*state.host_info, state.pretty_json).unwrap_or_default();
+        return Ok(json_response(&state, JSON_CONTENT_TYPE, json));
+    }
+
+    if state.metrics_endpoint && query.eq_ignore_ascii_case("METRICS") {
+        let json = to_json(&state.metrics.to_json(), state.pretty_json).unwrap_or_default();
+        return Ok(json_response(&state, JSON_CONTENT_TYPE, json));
+    }
+
+    if let Some(path) = query.strip_prefix("LISTEN=").filter(|_| state.listen_extension) {
+        return Ok(listen(&state, &req, path).await);
+    }
+
+    let wants_schema = query.eq_ignore_ascii_case("SCHEMA")
+        || req
+            .headers()
+            .get(ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .is_some_and(|accept| accept.contains("application/schema+json"));
+
+    let root = state.root.read().await;
+    let filtered = state.access_policy.filter(&root, &req);
+    let root = filtered.as_ref().unwrap_or(&root);
+
+    // `/schema` is the schema of the whole tree, shadowing any node there
+    let schema_path = uri.path() == "/schema";
+    let (path, wants_schema) = if schema_path { ("/", true) } else { (uri.path(), wants_schema) };
+
+    if path.contains('*') {
+        let matches: serde_json::Map<String, serde_json::Value> = OscNode::glob(root, path)
+            .into_iter()
+            .map(|node| (node.full_path.clone(), serde_json::to_value(node).unwrap_or_default()))
+            .collect();
+        let json = to_json(&matches, state.pretty_json).unwrap_or_else(|_| b"{}".to_vec());
+        return Ok(json_response(&state, JSON_CONTENT_TYPE, json));
+    }
+
+    let Some(root) = OscNode::get_node(root, path) else {
+        return Ok(error_response(&state, StatusCode::NOT_FOUND, "path not found"));
+    };
+
+    if wants_schema {
+        let schema = OscNode::to_json_schema(root);
+        let json = to_json(&schema, state.pretty_json).unwrap_or_else(|_| b"{}".to_vec());
+        return Ok(json_response(&state, SCHEMA_CONTENT_TYPE, json));
+    }
+
+    let json = to_json(root, state.pretty_json).unwrap_or_else(|_| b"{}".to_vec());
+
+    Ok(json_response(&state, JSON_CONTENT_TYPE, json))
+}
+
+fn host_allowed(allowed: &[String], req: &Request<Incoming>) -> bool {
+    if allowed.is_empty() {
+        return true;
+    }
+
+    let Some(host) = req.headers().get(HOST).and_then(|host| host.to_str().ok()) else {
+        return false;
+    };
+    let hostname = match host.rsplit_once(':') {
+        // Leave bracketed IPv6 literals without a port alone
+        Some((name, port)) if !port.contains(']') => name,
+        _ => host,
+    };
+    allowed
+        .iter()
+        .any(|entry| entry.eq_ignore_ascii_case(host) || entry.eq_ignore_ascii_case(hostname))
+}
+
+async fn serve_connection<S>(
+    stream: S,
+    remote_addr: SocketAddr,
+    state: SharedState,
+    keep_alive: bool,
+    idle_timeout: Duration,
+) where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+{
+    // Use an adapter to access something implementing `tokio::io` traits as if they implement
+    // `hyper::rt` IO traits.
+    let io = TokioIo::new(stream);
+
+    if let Err(err) = http1::Builder::new()
+        .timer(TokioTimer::new())
+        .keep_alive(keep_alive)
+        .header_read_timeout(idle_timeout)
+        // `service_fn` converts our function in a `Service`
+        .serve_connection(io, service_fn(|req| handle_request(req, remote_addr, state.clone())))
+        .await
+    {
+        log::warn!("Error serving connection: {:?}", err);
+    }
+}
+
+#[cfg(feature = "tls")]
+fn tls_acceptor(cert_pem: &[u8], key_pem: &[u8]) -> Result<tokio_rustls::TlsAcceptor, OscQueryServerError> {
+    use tokio_rustls::rustls::pki_types::pem::PemObject;
+    use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+    use tokio_rustls::rustls::{crypto, ServerConfig};
+
+    let invalid = |err: &dyn std::fmt::Display| OscQueryServerError::InvalidTls(err.to_string());
+
+    let certs = CertificateDer::pem_slice_iter(cert_pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| invalid(&err))?;
+    let key = PrivateKeyDer::from_pem_slice(key_pem).map_err(|err| invalid(&err))?;
+
+    let config = ServerConfig::builder_with_provider(Arc::new(crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|err| invalid(&err))?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|err| invalid(&err))?;
+    Ok(tokio_rustls::TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Waits for a change to `path`, answering with the node or a 204 on timeout
+///
+/// Paths hidden by the access policy are treated as missing, both before and
+/// after waiting.
+async fn listen(state: &SharedState, req: &Request<Incoming>, path: &str) -> Response<ResponseBody> {
+    // Clients usually percent-encode the slashes in a query value
+    let Some(path) = percent_decode(path) else {
+        return error_response(state, StatusCode::BAD_REQUEST, "invalid LISTEN path");
+    };
+    let visible = |root: &OscNode| {
+        let filtered = state.access_policy.filter(root, req);
+        let root = filtered.as_ref().unwrap_or(root);
+        OscNode::get_node(root, &path).cloned()
+    };
+
+    // Only changes sent after subscribing are received, so subscribe before
+    // looking, or a change in between would be missed
+    let mut changes = state.value_changes.subscribe();
+
+    // Changes are sent under the node's FULL_PATH, which also settles
+    // trailing slashes
+    let Some(full_path) = visible(&*state.root.read().await).map(|node| node.full_path) else {
+        return error_response(state, StatusCode::NOT_FOUND, "path not found");
+    };
+
+    let wait = async {
+        loop {
+            match changes.recv().await {
+                Ok(changed) if changed == full_path => return true,
+                Ok(_) => {}
+                // Missed changes might include this path
+                Err(broadcast::error::RecvError::Lagged(_)) => return true,
+                Err(broadcast::error::RecvError::Closed) => return false,
+            }
+        }
+    };
+
+    if !matches!(tokio::time::timeout(LISTEN_TIMEOUT, wait).await, Ok(true)) {
+        return response_builder(state, StatusCode::NO_CONTENT)
+            .body(ResponseBody::default())
+            .unwrap();
     }
 
-    let root = state.root.read().unwrap();
-    let json = serde_json::to_string(&*root).unwrap_or_else(|_| "{}".to_string());
+    let Some(node) = visible(&*state.root.read().await) else {
+        return error_response(state, StatusCode::NOT_FOUND, "path not found");
+    };
+    let json = to_json(&node, state.pretty_json).unwrap_or_else(|_| b"{}".to_vec());
+    json_response(state, JSON_CONTENT_TYPE, json)
+}
+
+/// Body type of every response
+///
+/// The JSON is fully buffered for now. Swapping this for a streaming body
+/// only needs `json_response` and `to_json` to change.
+type ResponseBody = Full<Bytes>;
 
-    Ok(Response::builder()
-        .status(StatusCode::OK)
-        .header("Content-Type", "application/json")
-        .body(json)
-        .unwrap())
+/// Decode `%XX` escapes, or `None` if one is malformed or the result isn't UTF-8
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
 }
 
+/// Some strict HTTP stacks refuse JSON without an explicit charset
+const JSON_CONTENT_TYPE: &str = "application/json; charset=utf-8";
+const SCHEMA_CONTENT_TYPE: &str = "application/schema+json; charset=utf-8";
+
+fn json_response(state: &SharedState, content_type: &str, json: Vec<u8>) -> Response<ResponseBody> {
+    json_response_with_status(state, StatusCode::OK, content_type, json)
+}
+
+fn json_response_with_status(
+    state: &SharedState,
+    status: StatusCode,
+    content_type: &str,
+    json: Vec<u8>,
+) -> Response<ResponseBody> {
+    state.metrics.bytes_sent.fetch_add(json.len() as u64, Ordering::Relaxed);
+    response_builder(state, status)
+        .header("Content-Type", content_type)
+        .header(CONTENT_LENGTH, json.len())
+        .body(Full::new(Bytes::from(json)))
+        .unwrap()
+}
+
+const ALLOWED_METHODS: &str = "GET, OPTIONS";
+
+/// Empty `204` for `OPTIONS`, telling browsers to go ahead with the `GET`
+///
+/// CORS is set up through `with_response_header`; the allowed methods are
+/// only added when an `Access-Control-Allow-Origin` header is configured.
+fn preflight_response(state: &SharedState) -> Response<ResponseBody> {
+    let mut builder = response_builder(state, StatusCode::NO_CONTENT).header(ALLOW, ALLOWED_METHODS);
+    let cors = state
+        .response_headers
+        .iter()
+        .any(|(name, _)| name == ACCESS_CONTROL_ALLOW_ORIGIN);
+    if cors {
+        builder = builder.header(ACCESS_CONTROL_ALLOW_METHODS, ALLOWED_METHODS);
+    }
+    builder.body(ResponseBody::default()).unwrap()
+}
+
+/// `status` with an [`OscQueryErrorResponse`] body
+fn error_response(state: &SharedState, status: StatusCode, description: &str) -> Response<ResponseBody> {
+    let body = OscQueryErrorResponse {
+        code: status.as_u16(),
+        description: description.to_string(),
+    };
+    let json = to_json(&body, state.pretty_json).unwrap_or_default();
+    json_response_with_status(state, status, JSON_CONTENT_TYPE, json)
+}
+
+/// Response builder with the configured custom headers already applied
+fn response_builder(state: &SharedState, status: StatusCode) -> hyper::http::response::Builder {
+    let mut builder = Response::builder().status(status);
+    for (name, value) in state.response_headers.iter() {
+        builder = builder.header(name, value);
+    }
+    builder
+}
+
+/// DNS-SD limit on a single `key=value` TXT entry
+const MAX_TXT_ENTRY_LEN: usize = 255;
+
+/// Non-empty printable ASCII without `=`, short enough to leave room for a value
+fn is_txt_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.len() < MAX_TXT_ENTRY_LEN - 1
+        && key.bytes().all(|b| (0x20..=0x7e).contains(&b) && b != b'=')
+}
+
+/// Checks keys and truncates values so every entry fits in a TXT record
+fn sanitize_txt_properties(props: &mut HashMap<String, String>) -> Result<(), OscQueryServerError> {
+    for (key, value) in props.iter_mut() {
+        if !is_txt_key(key) {
+            return Err(OscQueryServerError::InvalidServiceProperty(key.clone()));
+        }
+
+        let max_len = MAX_TXT_ENTRY_LEN - key.len() - 1;
+        if value.len() > max_len {
+            let mut end = max_len;
+            while !value.is_char_boundary(end) {
+                end -= 1;
+            }
+            log::warn!("Truncating mDNS TXT property {} to {} bytes", key, end);
+            value.truncate(end);
+        }
+    }
+    Ok(())
+}
+
+fn is_dns_label(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 63
+        && !name.starts_with('-')
+        && !name.ends_with('-')
+        && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+}
+
+fn to_json<T: Serialize + ?Sized>(value: &T, pretty: bool) -> serde_json::Result<Vec<u8>> {
+    if pretty {
+        serde_json::to_vec_pretty(value)
+    } else {
+        serde_json::to_vec(value)
+    }
+}
+
+/// Tree edit for [`RunningServer::command_sender`]
+#[derive(Debug, Clone)]
+pub enum NodeCommand {
+    /// Add a method with the given access and typetag, like [`OscNode::add_method`]
+    Add(String, Access, String),
+    /// Remove a node and everything below it
+    Remove(String),
+    /// Set a node's `VALUE`, waking `?LISTEN` requests for it
+    SetValue(String, serde_json::Value),
+    /// Like [`RunningServer::replace_subtree`]
+    ReplaceSubtree(String, OscNode),
+}
+
+impl NodeCommand {
+    /// Applies the command, returning the path whose `VALUE` changed if any
+    ///
+    /// Edits that would break `limits` fail without touching the tree.
+    fn apply(self, root: &mut OscNode, limits: TreeLimits) -> Result<Option<String>, OscNodeError> {
+        match self {
+            NodeCommand::Add(path, access, typetag) => {
+                let method = OscNode::try_new_method(&path, access, &typetag)?;
+                limits.check_insert(root, &path, &method)?;
+                OscNode::insert_method(root, method);
+                Ok(None)
+            }
+            NodeCommand::Remove(path) => match OscNode::remove_node(root, &path) {
+                Some(_) => Ok(None),
+                None => Err(OscNodeError::PathNotFound(path)),
+            },
+            NodeCommand::SetValue(path, value) => {
+                let node = OscNode::get_node_mut(root, &path)
+                    .ok_or_else(|| OscNodeError::PathNotFound(path.clone()))?;
+                if node.locked {
+                    return Err(OscNodeError::Locked(path));
+                }
+                if let Some(typetag) = &node.typetag {
+                    check_value_matches_typetag(typetag, &value).map_err(|source| {
+                        OscNodeError::InvalidValue { path: path.clone(), source }
+                    })?;
+                }
+                node.value = Some(value);
+                // `?LISTEN` matches on FULL_PATH, whatever form `path` came in
+                Ok(Some(node.full_path.clone()))
+            }
+            NodeCommand::ReplaceSubtree(path, mut subtree) => {
+                limits.check_insert(root, &path, &subtree)?;
+                if path.trim_matches('/').is_empty() {
+                    OscNode::recompute_full_paths(&mut subtree);
+                    *root = subtree;
+                } else {
+                    let target = OscNode::ensure_path(root, &path);
+                    subtree.full_path = target.full_path.clone();
+                    recompute_children(&mut subtree);
+                    *target = subtree;
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// `with_max_depth` and `with_max_nodes`, kept up after `build_and_run`
+#[derive(Debug, Clone, Copy)]
+struct TreeLimits {
+    max_depth: Option<usize>,
+    max_nodes: Option<usize>,
+}
+
+impl TreeLimits {
+    /// Checks putting `node` at `path`, in place of whatever is there now
+    ///
+    /// Counting methods walks the whole tree, so this is only done when a
+    /// limit is set.
+    fn check_insert(&self, root: &OscNode, path: &str, node: &OscNode) -> Result<(), OscNodeError> {
+        let components = lenient_components(path);
+
+        if let Some(max) = self.max_depth {
+            let depth = components.len() + OscNode::max_depth(node);
+            if depth > max {
+                return Err(OscNodeError::MaxDepthExceeded { depth, max });
+            }
+        }
+
+        if let Some(max) = self.max_nodes {
+            let replaced = OscNode::get_node(root, &format!("/{}", components.join("/")));
+            let count = method_count(root) - replaced.map_or(0, method_count) + method_count(node);
+            if count > max {
+                return Err(OscNodeError::NodeLimitExceeded { count, max });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What `with_max_nodes` counts
+fn method_count(root: &OscNode) -> usize {
+    OscNode::find(root, |node| node.typetag.is_some()).len()
+}
+
+/// Drains queued commands in batches, one write lock per batch
+///
+/// Ends once every sender, including the one in `RunningServer`, is dropped.
+fn spawn_command_task(
+    root: Arc<RwLock<OscNode>>,
+    limits: TreeLimits,
+    value_changes: broadcast::Sender<String>,
+    generation: Arc<AtomicU64>,
+) -> mpsc::Sender<NodeCommand> {
+    let (sender, mut receiver) = mpsc::channel::<NodeCommand>(NODE_COMMAND_BUFFER);
+    tokio::task::spawn(async move {
+        let mut batch = Vec::with_capacity(NODE_COMMAND_BUFFER);
+        while receiver.recv_many(&mut batch, NODE_COMMAND_BUFFER).await > 0 {
+            let mut tree = root.write().await;
+            let mut changed = Vec::new();
+            for command in batch.drain(..) {
+                match command.apply(&mut tree, limits) {
+                    Ok(path) => changed.extend(path),
+                    Err(err) => log::warn!("Skipping node command: {}", err),
+                }
+            }
+            generation.fetch_add(1, Ordering::Relaxed);
+            drop(tree);
+
+            for path in changed {
+                // Only fails when nobody is listening
+                value_changes.send(path).ok();
+            }
+        }
+    });
+    sender
+}
+
+/// Waits for the HTTP task to stop, then tears down everything else once
+///
+/// Runs on its own task so no [`RunningServer`] clone has to hold anything
+/// while waiting, and cleanup happens even if nobody waits.
+async fn finish_shutdown(
+    http_task: JoinHandle<()>,
+    mdns: Option<Arc<Mutex<ServiceDaemon>>>,
+    mdns_watchdog: Option<AbortHandle>,
+    service_names: Vec<String>,
+    access_log: Option<AccessLog>,
+    stopped: watch::Sender<bool>,
+) {
+    if let Err(err) = http_task.await {
+        if !err.is_cancelled() {
+            log::warn!("OSCQuery HTTP server task failed: {:?}", err);
+        }
+    }
+
+    if let Some(watchdog) = mdns_watchdog {
+        watchdog.abort();
+    }
+    if let Some(mdns) = mdns {
+        let mdns = mdns.lock().unwrap().clone();
+        for name in &service_names {
+            if let Err(err) = mdns.unregister(name) {
+                log::warn!("Failed to unregister mDNS service {}: {:?}", name, err);
+            }
+        }
+    }
+    if let Some(access_log) = access_log {
+        access_log.flush().await;
+    }
+    stopped.send_replace(true);
+}
+
+/// Handle to a running server
+///
+/// Clones are cheap and all refer to the same server: the same node tree,
+/// mDNS registrations and HTTP task.
+#[derive(Clone)]
 pub struct RunningServer {
-    pub _mdns: ServiceDaemon,
+    /// `None` when built `with_no_mdns`. Behind a mutex so
+    /// `with_mdns_auto_restart` can swap in a new daemon.
+    pub _mdns: Option<Arc<Mutex<ServiceDaemon>>>,
+    root: Arc<RwLock<OscNode>>,
+    /// Address the HTTP listener is bound to
+    local_addr: SocketAddr,
+    osc_port: u16,
+    value_changes: broadcast::Sender<String>,
+    /// Bumped on every change to the tree
+    generation: Arc<AtomicU64>,
+    limits: TreeLimits,
+    commands: mpsc::Sender<NodeCommand>,
+    http_abort: AbortHandle,
+    /// Flips to `true` once [`finish_shutdown`] has cleaned up
+    stopped: watch::Receiver<bool>,
+}
+
+impl RunningServer {
+    /// Address the HTTP server actually listens on
+    ///
+    /// Useful with `with_http_port(0)`, where the OS picks the port.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn http_port(&self) -> u16 {
+        self.local_addr.port()
+    }
+
+    /// Advertised OSC port, the one picked when using `with_osc_port_range`
+    pub fn osc_port(&self) -> u16 {
+        self.osc_port
+    }
+
+    /// Number of advertised nodes, containers included
+    pub async fn node_count(&self) -> usize {
+        OscNode::node_count(&*self.root.read().await)
+    }
+
+    /// Maximum nesting depth of the advertised tree, with `/` at 0
+    pub async fn tree_depth(&self) -> usize {
+        OscNode::max_depth(&*self.root.read().await)
+    }
+
+    /// Set the `VALUE` of the node at `path`
+    ///
+    /// The value has to fit the node's typetag, see
+    /// [`check_value_matches_typetag`], and the node must not be locked
+    /// with [`OscNode::lock`]. Wakes any `?LISTEN` requests waiting on that
+    /// path.
+    pub async fn set_value(&self, path: &str, value: serde_json::Value) -> Result<(), OscNodeError> {
+        let command = NodeCommand::SetValue(path.to_string(), value);
+        let changed = command.apply(&mut *self.root.write().await, self.limits)?;
+        self.generation.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(path) = changed {
+            self.value_changes.send(path).ok();
+        }
+        Ok(())
+    }
+
+    /// [`OscNode::lock`] on the served tree
+    pub async fn lock(&self, path: &str) -> bool {
+        OscNode::lock(&mut *self.root.write().await, path)
+    }
+
+    /// [`OscNode::unlock`] on the served tree
+    pub async fn unlock(&self, path: &str) -> bool {
+        OscNode::unlock(&mut *self.root.write().await, path)
+    }
+
+    /// [`OscNode::set_access`] on the served tree
+    ///
+    /// Bumps [`Self::generation`] when the node exists.
+    pub async fn set_access(&self, path: &str, access: Access) -> bool {
+        let found = OscNode::set_access(&mut *self.root.write().await, path, access);
+        if found {
+            self.generation.fetch_add(1, Ordering::Relaxed);
+        }
+        found
+    }
+
+    /// Swap out everything at `path` for `subtree` in one go
+    ///
+    /// Requests never see a half-replaced tree, which matters when e.g. all
+    /// avatar parameters change at once. `FULL_PATH`s in `subtree` are
+    /// rewritten to sit at `path` and missing parents are created.
+    ///
+    /// Fails, leaving the tree as it was, if the result would break
+    /// `with_max_depth` or `with_max_nodes`.
+    pub async fn replace_subtree(&self, path: &str, subtree: OscNode) -> Result<(), OscNodeError> {
+        let command = NodeCommand::ReplaceSubtree(path.to_string(), subtree);
+        command.apply(&mut *self.root.write().await, self.limits)?;
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Queue tree edits without waiting for the write lock
+    ///
+    /// A background task applies everything queued so far under a single
+    /// write lock, which keeps lock churn down during bursts of avatar
+    /// parameter updates. Commands that fail, e.g. removing a missing path,
+    /// are logged and skipped.
+    pub fn command_sender(&self) -> mpsc::Sender<NodeCommand> {
+        self.commands.clone()
+    }
+
+    /// Counter that changes whenever the tree does, for cache invalidation
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    /// Resolves once the HTTP server task has stopped
+    ///
+    /// The mDNS services stay registered until then; they are unregistered
+    /// and the access log is flushed before this returns. Every clone can
+    /// wait on the same server.
+    pub async fn wait_until_shutdown(self) {
+        let mut stopped = self.stopped;
+        // The sender only goes away after sending `true`
+        stopped.wait_for(|stopped| *stopped).await.ok();
+    }
+
+    /// Stop accepting connections, unregister from mDNS and flush the access log
+    ///
+    /// Connections that are already open are served until they close.
+    pub async fn shutdown(self) {
+        self.http_abort.abort();
+        self.wait_until_shutdown().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_info_round_trip() {
+        let info = HostInfo {
+            name: "TestApp".to_string(),
+            osc_ip: "127.0.0.1".to_string(),
+            osc_port: 9001,
+            osc_transport: "UDP".to_string(),
+            osc_port_tcp: None,
+            extensions: serde_json::json!({ "ACCESS": true, "VALUE": true }),
+            description: Some("test server".to_string()),
+            version: None,
+        };
+
+        let json = serde_json::to_string(&info).unwrap();
+        let parsed: HostInfo = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&parsed).unwrap(), json);
+        assert_eq!(parsed.name, "TestApp");
+        assert_eq!(parsed.osc_port, 9001);
+        assert_eq!(parsed.extensions["ACCESS"], true);
+    }
+
+    #[test]
+    fn host_info_without_extensions() {
+        let json = r#"{"NAME":"Remote","OSC_IP":"10.0.0.2","OSC_PORT":9000,"OSC_TRANSPORT":"UDP"}"#;
+        let parsed: HostInfo = serde_json::from_str(json).unwrap();
+        assert_eq!(parsed.extensions, serde_json::json!({}));
+        assert_eq!(parsed.osc_port_tcp, None);
+        assert_eq!(parsed.description, None);
+    }
 }
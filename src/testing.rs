@@ -0,0 +1,81 @@
+//! Helpers for tests against OSCQuery trees, enabled by the `testing` feature
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+
+use crate::node::{OscNode, OscNodeDiff};
+
+/// Panics with a readable diff unless `actual` and `expected` are equal
+///
+/// The message starts with the method counts from [`OscNodeDiff`], then
+/// lists every differing node in path order: `-` for `expected`, `+` for
+/// `actual`. Nodes that only differ in fields not shown on the line, like
+/// `OVERLOADS`, are marked `~` with both nodes' `Debug` output.
+#[track_caller]
+pub fn assert_tree_eq(actual: &OscNode, expected: &OscNode) {
+    if actual == expected {
+        return;
+    }
+
+    let delta = OscNodeDiff::diff(expected, actual);
+    let mut out = format!(
+        "node trees differ: {} methods added, {} removed, {} changed (- expected, + actual)\n",
+        delta.added.len(),
+        delta.removed.len(),
+        delta.changed.len()
+    );
+
+    let expected_nodes = nodes_by_path(expected);
+    let actual_nodes = nodes_by_path(actual);
+    let paths: BTreeSet<&str> = expected_nodes.keys().chain(actual_nodes.keys()).copied().collect();
+
+    for path in paths {
+        match (expected_nodes.get(path), actual_nodes.get(path)) {
+            (Some(expected), None) => writeln!(out, "- {}", describe(path, expected)).unwrap(),
+            (None, Some(actual)) => writeln!(out, "+ {}", describe(path, actual)).unwrap(),
+            (Some(expected), Some(actual)) => {
+                let (expected, actual) = (without_contents(expected), without_contents(actual));
+                if expected == actual {
+                    continue;
+                }
+                let (old, new) = (describe(path, &expected), describe(path, &actual));
+                if old != new {
+                    writeln!(out, "- {}\n+ {}", old, new).unwrap();
+                } else {
+                    writeln!(out, "~ {}\n    expected: {:?}\n    actual:   {:?}", path, expected, actual)
+                        .unwrap();
+                }
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+
+    panic!("{}", out);
+}
+
+fn nodes_by_path(root: &OscNode) -> BTreeMap<&str, &OscNode> {
+    root.iter().map(|node| (node.full_path.as_str(), node)).collect()
+}
+
+/// `path TYPE (Access) = VALUE`, like a line of the `Display` tree
+fn describe(path: &str, node: &OscNode) -> String {
+    let mut line = path.to_string();
+    if let Some(typetag) = &node.typetag {
+        write!(line, " {}", typetag).unwrap();
+    }
+    if let Some(access) = node.access {
+        write!(line, " ({})", access).unwrap();
+    }
+    if let Some(value) = &node.value {
+        write!(line, " = {}", value).unwrap();
+    }
+    line
+}
+
+/// Just the node's own fields, so parents don't show up for their children
+fn without_contents(node: &OscNode) -> OscNode {
+    OscNode {
+        contents: HashMap::new(),
+        ..node.clone()
+    }
+}
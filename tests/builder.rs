@@ -0,0 +1,55 @@
+mod common;
+
+use serde_json::json;
+use vrc_oscquery::node::{validate_access_consistency, Access, OscNode};
+use vrc_oscquery::server::OscQueryServerBuilder;
+
+#[tokio::test]
+async fn container_access_opt_in() {
+    let builder = OscQueryServerBuilder::new("BuilderTest", 9000)
+        .with_method_value("/haptics/intensity", Access::ReadWrite, "f", json!([0.0]))
+        .with_container_access("/haptics", Access::Read);
+    assert_eq!(validate_access_consistency(builder.inspect_tree()), ["/haptics"]);
+
+    let server = common::start(builder).await;
+    let haptics = common::get(server.local_addr(), "/haptics").await.json();
+    assert_eq!(haptics["ACCESS"], 1);
+    assert_eq!(haptics["CONTENTS"]["intensity"]["ACCESS"], 3);
+}
+
+#[test]
+fn cloned_builder_is_independent() {
+    let base = OscQueryServerBuilder::new("BuilderTest", 9000)
+        .with_vrchat_avatar_receiver()
+        .with_method_value("/haptics/intensity", Access::ReadWrite, "f", json!([0.0]));
+    let before = base.inspect_tree_json().unwrap();
+
+    let debug = base
+        .clone()
+        .with_vrchat_chatbox()
+        .with_method_value("/haptics/intensity", Access::Read, "f", json!([1.0]))
+        .with_pretty_json(true);
+
+    assert_eq!(base.inspect_tree_json().unwrap(), before);
+    assert!(OscNode::get_node(base.inspect_tree(), "/chatbox").is_none());
+    assert!(OscNode::get_node(debug.inspect_tree(), "/chatbox/input").is_some());
+    let intensity = |builder: &OscQueryServerBuilder| {
+        OscNode::get_node(builder.inspect_tree(), "/haptics/intensity").unwrap().value.clone()
+    };
+    assert_eq!(intensity(&base), Some(json!([0.0])));
+    assert_eq!(intensity(&debug), Some(json!([1.0])));
+    // Pretty printing is builder state too
+    assert!(!before.contains('\n'));
+    assert!(debug.inspect_tree_json().unwrap().contains('\n'));
+}
+
+#[test]
+fn vrchat_chatbox_typetags() {
+    let builder = OscQueryServerBuilder::new("BuilderTest", 9000).with_vrchat_chatbox();
+    let node = |path| OscNode::get_node(builder.inspect_tree(), path).unwrap();
+
+    assert_eq!(node("/chatbox/input").typetag.as_deref(), Some("sT"));
+    assert_eq!(node("/chatbox/input").access, Some(Access::Write));
+    assert_eq!(node("/chatbox/typing").typetag.as_deref(), Some("T"));
+    assert_eq!(node("/chatbox/typing").access, Some(Access::Write));
+}
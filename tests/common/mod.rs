@@ -0,0 +1,70 @@
+//! Bare-bones HTTP/1.1 client for poking at servers built `with_no_mdns`
+
+#![allow(dead_code)]
+
+use std::net::SocketAddr;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use vrc_oscquery::server::{OscQueryServerBuilder, RunningServer};
+
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Response {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).unwrap_or_else(|err| panic!("{}: {}", err, self.body))
+    }
+}
+
+/// Starts `builder` on an OS-picked port without advertising it
+pub async fn start(builder: OscQueryServerBuilder) -> RunningServer {
+    builder.with_no_mdns().with_http_port(0).build_and_run().await.unwrap()
+}
+
+pub async fn get(addr: SocketAddr, target: &str) -> Response {
+    request(addr, "GET", target, &[]).await
+}
+
+/// Sends one request with `Connection: close` and reads the whole response
+///
+/// `Host` defaults to `addr` unless it's in `headers`.
+pub async fn request(addr: SocketAddr, method: &str, target: &str, headers: &[(&str, &str)]) -> Response {
+    let mut head = format!("{} {} HTTP/1.1\r\nConnection: close\r\n", method, target);
+    if !headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("host")) {
+        head.push_str(&format!("Host: {}\r\n", addr));
+    }
+    for (name, value) in headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(head.as_bytes()).await.unwrap();
+    let mut raw = Vec::new();
+    stream.read_to_end(&mut raw).await.unwrap();
+    let raw = String::from_utf8(raw).unwrap();
+
+    let (head, body) = raw.split_once("\r\n\r\n").unwrap();
+    let mut lines = head.split("\r\n");
+    let status = lines.next().unwrap().split(' ').nth(1).unwrap().parse().unwrap();
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.to_string(), value.trim().to_string()))
+        .collect();
+    Response {
+        status,
+        headers,
+        body: body.to_string(),
+    }
+}
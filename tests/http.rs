@@ -0,0 +1,193 @@
+mod common;
+
+use serde_json::json;
+use vrc_oscquery::node::Access;
+use vrc_oscquery::server::{OscQueryErrorResponse, OscQueryServerBuilder, OscQueryServerError};
+
+fn builder() -> OscQueryServerBuilder {
+    OscQueryServerBuilder::new("HttpTest", 9000)
+        .with_method_value("/avatar/parameters/Voice", Access::Read, "f", json!([0.5]))
+}
+
+#[tokio::test]
+async fn allowed_hosts() {
+    let hosts = vec!["localhost".to_string(), "oscquery.test:8080".to_string()];
+    let server = common::start(builder().with_allowed_hosts(hosts)).await;
+    let addr = server.local_addr();
+    let get_with_host = |host: &'static str| async move {
+        common::request(addr, "GET", "/", &[("Host", host)]).await.status
+    };
+
+    assert_eq!(get_with_host("localhost").await, 200);
+    // Entries without a port match any port
+    assert_eq!(get_with_host("localhost:1234").await, 200);
+    assert_eq!(get_with_host("LOCALHOST").await, 200);
+    assert_eq!(get_with_host("oscquery.test:8080").await, 200);
+
+    assert_eq!(get_with_host("evil.example").await, 400);
+    assert_eq!(get_with_host("localhost.evil.example").await, 400);
+    assert_eq!(get_with_host("oscquery.test:9090").await, 400);
+    assert_eq!(get_with_host("oscquery.test").await, 400);
+}
+
+#[tokio::test]
+async fn no_allowed_hosts_means_any_host() {
+    let server = common::start(builder()).await;
+    let host = [("Host", "evil.example")];
+    let response = common::request(server.local_addr(), "GET", "/", &host).await;
+    assert_eq!(response.status, 200);
+}
+
+fn assert_error_body(response: &common::Response, code: u16) {
+    assert_eq!(response.status, code);
+    assert_eq!(response.header("content-type"), Some("application/json; charset=utf-8"));
+    let error: OscQueryErrorResponse = serde_json::from_str(&response.body).unwrap();
+    assert_eq!(error.code, code);
+    assert!(!error.description.is_empty());
+}
+
+#[tokio::test]
+async fn error_bodies() {
+    let hosts = vec!["localhost".to_string()];
+    let server = common::start(builder().with_allowed_hosts(hosts)).await;
+    let addr = server.local_addr();
+    let host = [("Host", "localhost")];
+
+    assert_error_body(&common::request(addr, "GET", "/", &[("Host", "evil.example")]).await, 400);
+    assert_error_body(&common::request(addr, "GET", "/missing", &host).await, 404);
+
+    let response = common::request(addr, "POST", "/", &host).await;
+    assert_error_body(&response, 405);
+    assert!(response.header("allow").is_some());
+}
+
+#[tokio::test]
+async fn rate_limit_error_body() {
+    let server = common::start(builder().with_rate_limit(1)).await;
+
+    // The per-second counter could reset between two requests, so keep going
+    // until one is refused
+    for _ in 0..10 {
+        let response = common::get(server.local_addr(), "/").await;
+        if response.status == 429 {
+            assert_error_body(&response, 429);
+            assert_eq!(response.header("retry-after"), Some("1"));
+            return;
+        }
+    }
+    panic!("never rate limited");
+}
+
+#[tokio::test]
+async fn local_only_error_body() {
+    // Needs a non-loopback address on this machine to connect from
+    let Some(ip) = if_addrs::get_if_addrs()
+        .unwrap()
+        .into_iter()
+        .map(|interface| interface.ip())
+        .find(|ip| ip.is_ipv4() && !ip.is_loopback())
+    else {
+        return;
+    };
+
+    let builder = builder()
+        .with_local_only()
+        .with_bind_ip(std::net::Ipv4Addr::UNSPECIFIED.into())
+        .with_no_mdns()
+        .with_http_port(0);
+    let server = builder.build_and_run().await.unwrap();
+    let port = server.local_addr().port();
+
+    assert_eq!(common::get((std::net::Ipv4Addr::LOCALHOST, port).into(), "/").await.status, 200);
+    assert_error_body(&common::get((ip, port).into(), "/").await, 403);
+}
+
+#[tokio::test]
+async fn initial_values_are_served() {
+    let value = json!(["hi", true, false]);
+    let builder = builder().with_method_value("/chatbox/input", Access::Write, "sTT", value.clone());
+    let server = common::start(builder).await;
+
+    let voice = common::get(server.local_addr(), "/avatar/parameters/Voice").await.json();
+    assert_eq!(voice["VALUE"], json!([0.5]));
+
+    let root = common::get(server.local_addr(), "/").await.json();
+    assert_eq!(root["CONTENTS"]["chatbox"]["CONTENTS"]["input"]["VALUE"], value);
+    // Containers and methods without a value don't get one
+    assert!(root["CONTENTS"]["chatbox"].get("VALUE").is_none());
+}
+
+#[tokio::test]
+async fn mismatched_initial_value_fails_build() {
+    let result = builder()
+        .with_method_value("/bad", Access::Read, "i", json!(["not an int"]))
+        .with_no_mdns()
+        .with_http_port(0)
+        .build_and_run()
+        .await;
+    assert!(matches!(result, Err(OscQueryServerError::InvalidNode(_))));
+}
+
+#[tokio::test]
+async fn wildcard_queries() {
+    let builder = builder()
+        .with_method_value("/avatar/parameters/Gesture", Access::Read, "i", json!([2]))
+        .with_method_value("/tracking/head/Voice", Access::Read, "f", json!([0.0]));
+    let server = common::start(builder).await;
+
+    let single = common::get(server.local_addr(), "/avatar/parameters/*").await;
+    assert_eq!(single.status, 200);
+    let single = single.json();
+    let mut paths: Vec<&String> = single.as_object().unwrap().keys().collect();
+    paths.sort();
+    assert_eq!(paths, ["/avatar/parameters/Gesture", "/avatar/parameters/Voice"]);
+    assert_eq!(single["/avatar/parameters/Gesture"]["VALUE"], json!([2]));
+
+    let any_depth = common::get(server.local_addr(), "/**/Voice").await.json();
+    let mut paths: Vec<&String> = any_depth.as_object().unwrap().keys().collect();
+    paths.sort();
+    assert_eq!(paths, ["/avatar/parameters/Voice", "/tracking/head/Voice"]);
+
+    // No match is an empty object rather than a 404
+    let none = common::get(server.local_addr(), "/nothing/*").await;
+    assert_eq!(none.status, 200);
+    assert_eq!(none.json(), json!({}));
+}
+
+#[tokio::test]
+async fn plain_paths() {
+    let server = common::start(builder()).await;
+
+    let subtree = common::get(server.local_addr(), "/avatar").await;
+    assert_eq!(subtree.status, 200);
+    let subtree = subtree.json();
+    assert_eq!(subtree["FULL_PATH"], "/avatar");
+    assert_eq!(subtree["CONTENTS"]["parameters"]["CONTENTS"]["Voice"]["TYPE"], "f");
+
+    assert_eq!(common::get(server.local_addr(), "/avatar/missing").await.status, 404);
+    assert_eq!(common::get(server.local_addr(), "/avatar//parameters").await.status, 404);
+}
+
+#[tokio::test]
+async fn json_content_type_has_charset() {
+    let server = common::start(builder().with_metrics_endpoint()).await;
+
+    let targets = [
+        "/",
+        "/avatar/parameters/Voice",
+        "/?HOST_INFO",
+        "/HOST_INFO",
+        "/avatar/*",
+        "/?METRICS",
+        "/missing",
+    ];
+    for target in targets {
+        let response = common::get(server.local_addr(), target).await;
+        assert_eq!(
+            response.header("content-type"),
+            Some("application/json; charset=utf-8"),
+            "{}",
+            target
+        );
+    }
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+use vrc_oscquery::client::OscQueryError;
+use vrc_oscquery::http_client::OscQueryHttpClient;
+use vrc_oscquery::node::Access;
+use vrc_oscquery::server::OscQueryServerBuilder;
+
+#[tokio::test]
+async fn get_node_adds_the_leading_slash() {
+    let builder = OscQueryServerBuilder::new("HttpClientTest", 9000)
+        .with_method_value("/avatar/parameters/Voice", Access::Read, "f", json!([0.5]));
+    let server = common::start(builder).await;
+    let client = OscQueryHttpClient::new(format!("http://{}", server.local_addr()));
+
+    let node = client.get_node("avatar/parameters/Voice").await.unwrap();
+    assert_eq!(node.full_path, "/avatar/parameters/Voice");
+    assert!(matches!(
+        client.get_node("avatar/missing").await,
+        Err(OscQueryError::NodeNotFound(path)) if path == "/avatar/missing"
+    ));
+}
+
+#[tokio::test]
+async fn requests_time_out() {
+    // Accepts the connection but never answers
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let _silent = tokio::spawn(async move {
+        let mut held = Vec::new();
+        while let Ok((stream, _)) = listener.accept().await {
+            held.push(stream);
+        }
+    });
+
+    let client =
+        OscQueryHttpClient::new(format!("http://{}", addr)).with_timeout(Duration::from_millis(200));
+    assert_eq!(client.timeout(), Duration::from_millis(200));
+
+    let started = Instant::now();
+    assert!(matches!(client.get_tree().await, Err(OscQueryError::Http { .. })));
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
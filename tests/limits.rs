@@ -0,0 +1,59 @@
+mod common;
+
+use std::time::Duration;
+
+use serde_json::json;
+use vrc_oscquery::node::{Access, OscNode, OscNodeError};
+use vrc_oscquery::server::{NodeCommand, OscQueryServerBuilder};
+
+fn builder() -> OscQueryServerBuilder {
+    OscQueryServerBuilder::new("LimitsTest", 9000)
+        .with_method_value("/a/x", Access::ReadWrite, "i", json!([0]))
+        .with_method_value("/a/y", Access::ReadWrite, "i", json!([0]))
+}
+
+#[tokio::test]
+async fn commands_respect_max_nodes() {
+    let server = common::start(builder().with_max_nodes(2)).await;
+
+    let commands = server.command_sender();
+    commands
+        .send(NodeCommand::Add("/a/z".to_string(), Access::Read, "f".to_string()))
+        .await
+        .unwrap();
+    // Replacing an existing method doesn't add one
+    commands
+        .send(NodeCommand::Add("/a/y".to_string(), Access::Read, "f".to_string()))
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(server.node_count().await, 4);
+
+    let mut subtree = OscNode::new_container("/b");
+    OscNode::add_method(&mut subtree, "/one", Access::Read, "i");
+    assert_eq!(
+        server.replace_subtree("/b", subtree.clone()).await,
+        Err(OscNodeError::NodeLimitExceeded { count: 3, max: 2 })
+    );
+    // Swapping out `/a` frees up its two methods
+    server.replace_subtree("/a", subtree).await.unwrap();
+    assert_eq!(server.node_count().await, 3);
+}
+
+#[tokio::test]
+async fn commands_respect_max_depth() {
+    let server = common::start(builder().with_max_depth(2)).await;
+
+    let mut subtree = OscNode::new_container("/deep");
+    OscNode::add_method(&mut subtree, "/er", Access::Read, "i");
+    assert_eq!(
+        server.replace_subtree("/a/x", subtree.clone()).await,
+        Err(OscNodeError::MaxDepthExceeded { depth: 3, max: 2 })
+    );
+    server.replace_subtree("/a", subtree).await.unwrap();
+
+    let add = NodeCommand::Add("/a/b/c".to_string(), Access::Read, "i".to_string());
+    server.command_sender().send(add).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(server.tree_depth().await, 2);
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::json;
+use vrc_oscquery::node::Access;
+use vrc_oscquery::server::{AccessPolicy, NodeCommand, OscQueryServerBuilder};
+
+fn builder() -> OscQueryServerBuilder {
+    OscQueryServerBuilder::new("ListenTest", 9000)
+        .with_listen_extension()
+        .with_method_value("/public/a", Access::ReadWrite, "i", json!([0]))
+        .with_method_value("/secret/b", Access::ReadWrite, "i", json!([0]))
+        .with_access_control(AccessPolicy::Custom(Arc::new(|path, _| !path.starts_with("/secret"))))
+}
+
+#[tokio::test]
+async fn listen_respects_access_policy() {
+    let server = common::start(builder()).await;
+
+    let response = common::get(server.local_addr(), "/?LISTEN=/secret/b").await;
+    assert_eq!(response.status, 404);
+
+    let addr = server.local_addr();
+    let listener = tokio::spawn(async move { common::get(addr, "/?LISTEN=/public/a").await });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    server.set_value("/public/a", json!([7])).await.unwrap();
+
+    let response = listener.await.unwrap();
+    assert_eq!(response.status, 200);
+    assert_eq!(response.json()["VALUE"], json!([7]));
+}
+
+#[tokio::test]
+async fn listen_wakes_when_another_path_changes_right_after() {
+    let server = common::start(builder()).await;
+
+    let addr = server.local_addr();
+    let listener = tokio::spawn(async move { common::get(addr, "/?LISTEN=/public/a").await });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // Both end up in the same batch, so the two changes go out back to back
+    let commands = server.command_sender();
+    commands
+        .send(NodeCommand::SetValue("/public/a".to_string(), json!([1])))
+        .await
+        .unwrap();
+    commands
+        .send(NodeCommand::SetValue("/secret/b".to_string(), json!([2])))
+        .await
+        .unwrap();
+
+    let response = tokio::time::timeout(Duration::from_secs(5), listener)
+        .await
+        .expect("listener missed the change")
+        .unwrap();
+    assert_eq!(response.status, 200);
+    assert_eq!(response.json()["VALUE"], json!([1]));
+}
+
+#[tokio::test]
+async fn listen_path_is_normalised() {
+    let server = common::start(builder()).await;
+
+    let addr = server.local_addr();
+    let listener = tokio::spawn(async move { common::get(addr, "/?LISTEN=%2Fpublic%2Fa/").await });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    server.set_value("/public/a", json!([3])).await.unwrap();
+
+    let response = tokio::time::timeout(Duration::from_secs(5), listener)
+        .await
+        .expect("listener missed the change")
+        .unwrap();
+    assert_eq!(response.status, 200);
+    assert_eq!(response.json()["VALUE"], json!([3]));
+
+    let response = common::get(server.local_addr(), "/?LISTEN=%2").await;
+    assert_eq!(response.status, 400);
+}
@@ -0,0 +1,34 @@
+mod common;
+
+use serde_json::json;
+use vrc_oscquery::node::Access;
+use vrc_oscquery::server::OscQueryServerBuilder;
+
+#[tokio::test]
+async fn two_servers_run_side_by_side() {
+    let avatar = OscQueryServerBuilder::new("AvatarTree", 9000)
+        .with_vrchat_avatar_receiver()
+        .with_no_mdns()
+        .with_http_port(0)
+        .build_and_run();
+    let haptics = OscQueryServerBuilder::new("HapticsTree", 9001)
+        .with_method_value("/haptics/intensity", Access::ReadWrite, "f", json!([0.5]))
+        .with_no_mdns()
+        .with_http_port(0)
+        .build_and_run();
+
+    let (avatar, haptics) = tokio::join!(avatar, haptics);
+    let (avatar, haptics) = (avatar.unwrap(), haptics.unwrap());
+    assert_ne!(avatar.local_addr(), haptics.local_addr());
+
+    let info = common::get(avatar.local_addr(), "/?HOST_INFO").await.json();
+    assert_eq!(info["NAME"], "AvatarTree");
+    let info = common::get(haptics.local_addr(), "/?HOST_INFO").await.json();
+    assert_eq!(info["NAME"], "HapticsTree");
+
+    assert_eq!(common::get(avatar.local_addr(), "/avatar/change").await.status, 200);
+    assert_eq!(common::get(avatar.local_addr(), "/haptics").await.status, 404);
+    let intensity = common::get(haptics.local_addr(), "/haptics/intensity").await.json();
+    assert_eq!(intensity["VALUE"], json!([0.5]));
+    assert_eq!(common::get(haptics.local_addr(), "/avatar").await.status, 404);
+}
@@ -0,0 +1,21 @@
+mod common;
+
+use serde_json::json;
+use vrc_oscquery::node::{Access, OscNode};
+use vrc_oscquery::server::OscQueryServerBuilder;
+
+#[tokio::test]
+async fn node_count_and_tree_depth() {
+    let builder = OscQueryServerBuilder::new("StatsTest", 9000)
+        .with_method_value("/avatar/parameters/Voice", Access::Read, "f", json!([0.0]))
+        .with_method_value("/chatbox/typing", Access::Write, "T", json!([true]));
+    let server = common::start(builder).await;
+
+    // `/`, `/avatar`, `/avatar/parameters`, `/chatbox` and the two methods
+    assert_eq!(server.node_count().await, 6);
+    assert_eq!(server.tree_depth().await, 3);
+
+    server.replace_subtree("/avatar", OscNode::new_container("/avatar")).await.unwrap();
+    assert_eq!(server.node_count().await, 4);
+    assert_eq!(server.tree_depth().await, 2);
+}
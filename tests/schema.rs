@@ -0,0 +1,40 @@
+mod common;
+
+use serde_json::json;
+use vrc_oscquery::node::Access;
+use vrc_oscquery::server::OscQueryServerBuilder;
+
+fn builder() -> OscQueryServerBuilder {
+    OscQueryServerBuilder::new("SchemaTest", 9000)
+        .with_method_value("/avatar/parameters/Volume", Access::ReadWrite, "f", json!([0.5]))
+}
+
+#[tokio::test]
+async fn schema_endpoint() {
+    let server = common::start(builder()).await;
+
+    let response = common::get(server.local_addr(), "/schema").await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.header("content-type"), Some("application/schema+json; charset=utf-8"));
+    let schema = response.json();
+    assert_eq!(schema["$schema"], "http://json-schema.org/draft-07/schema#");
+    assert!(response.body.contains("/avatar/parameters/Volume"), "{}", response.body);
+}
+
+#[tokio::test]
+async fn schema_by_query_or_accept_header() {
+    let server = common::start(builder()).await;
+    let whole = common::get(server.local_addr(), "/schema").await.json();
+
+    let by_query = common::get(server.local_addr(), "/?SCHEMA").await;
+    assert_eq!(by_query.json(), whole);
+
+    let accept = [("Accept", "application/schema+json")];
+    let by_header = common::request(server.local_addr(), "GET", "/", &accept).await;
+    assert_eq!(by_header.json(), whole);
+
+    // Other paths give the schema for just that subtree
+    let subtree = common::get(server.local_addr(), "/avatar?SCHEMA").await;
+    assert_eq!(subtree.status, 200);
+    assert_ne!(subtree.json(), whole);
+}
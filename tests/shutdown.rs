@@ -0,0 +1,25 @@
+use std::time::Duration;
+
+use vrc_oscquery::server::OscQueryServerBuilder;
+
+#[tokio::test]
+async fn shutdown_while_another_clone_waits() {
+    let server = OscQueryServerBuilder::new("ShutdownTest", 9000)
+        .with_no_mdns()
+        .with_http_port(0)
+        .build_and_run()
+        .await
+        .unwrap();
+
+    let waiter = tokio::spawn(server.clone().wait_until_shutdown());
+    // Let the waiter start waiting before shutting down from the other clone
+    tokio::task::yield_now().await;
+
+    tokio::time::timeout(Duration::from_secs(5), server.shutdown())
+        .await
+        .expect("shutdown deadlocked");
+    tokio::time::timeout(Duration::from_secs(5), waiter)
+        .await
+        .expect("waiter never woke up")
+        .unwrap();
+}
@@ -0,0 +1,80 @@
+use std::collections::BTreeSet;
+
+use proptest::prelude::*;
+use vrc_oscquery::node::{ancestors, Access, OscNode};
+
+const TYPETAGS: &[&str] = &[
+    "i", "f", "s", "b", "h", "t", "d", "S", "c", "r", "m", "T", "F", "N", "I", "ff", "sTT", "[if]",
+];
+
+fn access() -> impl Strategy<Value = Access> {
+    prop_oneof![
+        Just(Access::None),
+        Just(Access::Read),
+        Just(Access::Write),
+        Just(Access::ReadWrite),
+    ]
+}
+
+fn method() -> impl Strategy<Value = (String, &'static str, Access)> {
+    let path = prop::collection::vec("[a-zA-Z0-9-]{1,4}", 1..5)
+        .prop_map(|parts| format!("/{}", parts.join("/")));
+    (path, prop::sample::select(TYPETAGS), access())
+}
+
+/// The tree for `methods`, plus how many containers it should have
+///
+/// Methods that would land on or under an earlier node are skipped, as
+/// replacing nodes would make the count depend on insertion order.
+fn build(methods: &[(String, &str, Access)]) -> (OscNode, usize, usize) {
+    let mut root = OscNode::new_container("/");
+    let mut containers = BTreeSet::new();
+    let mut added = BTreeSet::new();
+
+    for (path, typetag, access) in methods {
+        let parents: Vec<&str> = ancestors(path).into_iter().skip(1).collect();
+        if containers.contains(path.as_str())
+            || added.contains(path.as_str())
+            || parents.iter().any(|parent| added.contains(parent))
+        {
+            continue;
+        }
+        OscNode::add_method(&mut root, path, *access, typetag);
+        containers.extend(parents);
+        added.insert(path.as_str());
+    }
+    (root, containers.len(), added.len())
+}
+
+proptest! {
+    #[test]
+    fn node_count_is_methods_plus_containers(methods in prop::collection::vec(method(), 0..40)) {
+        let (root, containers, methods) = build(&methods);
+        // The root is a container too
+        prop_assert_eq!(OscNode::node_count(&root), 1 + containers + methods);
+    }
+
+    #[test]
+    fn full_paths_start_with_slash(methods in prop::collection::vec(method(), 0..40)) {
+        let (root, _, _) = build(&methods);
+        for node in root.iter() {
+            prop_assert!(node.full_path.starts_with('/'), "{:?}", node.full_path);
+        }
+    }
+
+    #[test]
+    fn json_round_trip_is_stable(methods in prop::collection::vec(method(), 0..40)) {
+        let (root, _, _) = build(&methods);
+        // serde_json::Value sorts keys, so this is stable across HashMaps
+        let first = serde_json::to_value(&root).unwrap().to_string();
+        let reparsed: OscNode = serde_json::from_str(&first).unwrap();
+        let second = serde_json::to_value(&reparsed).unwrap().to_string();
+        prop_assert_eq!(first, second);
+    }
+
+    #[test]
+    fn iter_visits_every_node(methods in prop::collection::vec(method(), 0..40)) {
+        let (root, _, _) = build(&methods);
+        prop_assert_eq!(root.iter().count(), OscNode::node_count(&root));
+    }
+}